@@ -1,24 +1,58 @@
+use std::collections::HashMap;
+use crate::error::{Error, Result};
+
+/// Size of a DNS message over UDP without EDNS
+pub const UDP_PACKET_SIZE: usize = 512;
+/// Largest possible DNS message, limited by the two byte length prefix of TCP
+pub const MAX_PACKET_SIZE: usize = 65535;
 
-type Error = Box<dyn std::error::Error>;
-type Result<T> = std::result::Result<T, Error>;
 /* == BytePacketBuffer == */
 /// Represents the Dns packet in bytes
-
 pub struct BytePacketBuffer {
-    pub buf: [u8; 512],
-    pub pos: usize // position we are reading
+    pub buf: Vec<u8>,
+    pub pos: usize, // position we are reading
+    names: HashMap<String, u16> // offsets of the names written, for compression
+}
+
+impl Default for BytePacketBuffer {
+    fn default() -> Self {
+        BytePacketBuffer::new()
+    }
 }
 
 impl BytePacketBuffer {
 
-    /// Gives us a fresh buffer for the packet contents.
+    /// Gives us a fresh buffer for the packet contents, sized for UDP.
     pub fn new() -> BytePacketBuffer {
+        BytePacketBuffer::with_size(UDP_PACKET_SIZE)
+    }
+
+    /// Gives us a fresh buffer of 'size' bytes, at most 65535.
+    /// Writing past the size fails with 'Error::BufferFull', so that the
+    /// caller can truncate the message instead.
+    pub fn with_size(size: usize) -> BytePacketBuffer {
         BytePacketBuffer {
-            buf: [0; 512],
-            pos: 0
+            buf: vec![0; size.min(MAX_PACKET_SIZE)],
+            pos: 0,
+            names: HashMap::new()
         }
     }
 
+    /// Change the size of the buffer, e.g. to the number of bytes received
+    pub fn resize(&mut self, size: usize) {
+        self.buf.resize(size.min(MAX_PACKET_SIZE), 0);
+    }
+
+    /// Size of the buffer, the limit of every read and write
+    pub fn size(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Whether 'len' bytes starting at 'start' are within the buffer
+    fn in_bounds(&self, start: usize, len: usize) -> bool {
+        start.checked_add(len).is_some_and(|end| end <= self.size())
+    }
+
     /* ---- Read part ---- */
 
     /// Current position within the buffer
@@ -28,28 +62,28 @@ impl BytePacketBuffer {
 
     /// Step the buffer position forward a specific number of times
     pub fn step(&mut self, steps: usize) -> Result<()> {
-        if self.pos + steps < self.buf.len() {
+        if self.in_bounds(self.pos, steps) {
             self.pos += steps;
             return Ok(());
         }
 
-        Err("Buffer overflow".into())
+        Err(Error::Truncated { offset: self.size() })
     }
 
     /// Change the buffer position
     pub fn seek(&mut self, pos: usize) -> Result<()> {
-        if pos < self.buf.len() {
+        if pos <= self.size() {
             self.pos = pos;
             return Ok(());
         }
 
-        Err("Out of buffer".into())
+        Err(Error::Truncated { offset: self.size() })
     }
 
     /// Read a single byte and step forward
     pub fn read(&mut self) -> Result<u8> {
-        if self.pos >= 512 {
-            return Err("End of buffer (read function)".into());
+        if !self.in_bounds(self.pos, 1) {
+            return Err(Error::Truncated { offset: self.pos });
         }
         let res = self.buf[self.pos];
         self.pos += 1;
@@ -59,16 +93,16 @@ impl BytePacketBuffer {
 
     /// Get a single byte
     pub fn get(&self, pos: usize) -> Result<u8> {
-        if pos >= 512 {
-            return Err("End of buffer (get function)".into());
+        if !self.in_bounds(pos, 1) {
+            return Err(Error::Truncated { offset: pos });
         }
         Ok(self.buf[pos])
     }
 
     /// Get a range of bytes
     pub fn get_range(&self, start: usize, len: usize) -> Result<&[u8]> {
-        if start + len >= 512 {
-            return Err("End of buffer (get_range function)".into());
+        if !self.in_bounds(start, len) {
+            return Err(Error::Truncated { offset: start });
         }
         Ok(&self.buf[start..start + len])
     }
@@ -91,6 +125,20 @@ impl BytePacketBuffer {
         Ok(res)
     }
 
+    /// Read 'len' bytes, stepping forward past them
+    pub fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let res = self.get_range(self.pos, len)?.to_vec();
+        self.step(len)?;
+
+        Ok(res)
+    }
+
+    /// Read a character-string: a length byte followed by up to 255 bytes
+    pub fn read_character_string(&mut self) -> Result<Vec<u8>> {
+        let len = self.read()? as usize;
+        self.read_bytes(len)
+    }
+
     /// Read a qname
     ///
     /// Reading domain names, taking labels into considerationa
@@ -107,6 +155,7 @@ impl BytePacketBuffer {
         let mut jumped = false;
         let max_jumps = 5; // maximum number of jumps to avoid infinite cycles
         let mut jumps_performed = 0;
+        let mut name_len = 1; // length on the wire, with the final empty label
 
         // String delimiter at first empty, then initialized to '.'
         let mut delim = "";
@@ -115,7 +164,7 @@ impl BytePacketBuffer {
             // The packet could be crafted with a cycle in the jump instructions
             // to perform unlimited jumps
             if jumps_performed > max_jumps {
-                return Err(format!("Limit of {} jumps exceeded", max_jumps).into());
+                return Err(Error::PointerLoop { offset: pos });
             }
 
             // Beginning of label. Lenght in bytes of label
@@ -141,6 +190,11 @@ impl BytePacketBuffer {
                 jumps_performed += 1;
             }
 
+            // The other combinations of the two bits were never put to use
+            else if (len & 0xC0) != 0 {
+                return Err(Error::BadLabel { offset: pos });
+            }
+
             // Base scenario, we are reading a single label and appendig
             // it to the output
             else {
@@ -151,6 +205,11 @@ impl BytePacketBuffer {
                     break;
                 }
 
+                name_len += len as usize + 1;
+                if name_len > 255 {
+                    return Err(Error::NameTooLong { offset: pos - 1 });
+                }
+
                 outstr.push_str(delim);
                 // Excract actual ASCII bytes
                 let str = self.get_range(pos, len as usize)?;
@@ -176,8 +235,8 @@ impl BytePacketBuffer {
 
     /// Write a byte on the buffer at the current position
     pub fn write(&mut self, val: u8) -> Result<()> {
-        if self.pos >= 512 {
-            return Err("End of buffer (write function)".into());
+        if !self.in_bounds(self.pos, 1) {
+            return Err(Error::BufferFull);
         }
         self.buf[self.pos] = val;
         self.pos += 1;
@@ -209,45 +268,73 @@ impl BytePacketBuffer {
         Ok(())
     }
 
+    /// Write a sequence of bytes as they are
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        for b in bytes {
+            self.write(*b)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a character-string, its length must fit in a single byte
+    pub fn write_character_string(&mut self, bytes: &[u8]) -> Result<()> {
+        if bytes.len() > 0xFF {
+            return Err(Error::Invalid(format!("Character-string of {} bytes exceeds 255", bytes.len())));
+        }
+        self.write_u8(bytes.len() as u8)?;
+        self.write_bytes(bytes)
+    }
+
     /// Write a query name in labeled form
+    ///
+    /// Names are compressed (RFC 1035 4.1.4): when the rest of the name was
+    /// already written, a pointer to its offset replaces the remaining labels.
+    /// Example: mail.google.com after www.google.com -> [4]mail[0xC0 0x10]
     pub fn write_qname(&mut self, qname: &str) -> Result<()> {
-        let mut len = 0;
-        let mut at = 0usize;
-        for c in qname.chars() {
-            if c == '.' {
-                self.write_u8(len as u8)?;
-                for i in 0..len {
-                    self.write_u8(qname.chars().nth(at + i).unwrap() as u8)?;
-                }
-                at = at + len + 1;
-                len = 0;
-            }
-            else {
-                len += 1;
-            }
-        }
+        self.write_labels(qname, true)
+    }
 
-        self.write_u8(len as u8)?;
-        for i in 0..len {
-            self.write_u8(qname.chars().nth(at + i).unwrap() as u8)?;
+    /// Write a query name in labeled form without compression, for the names
+    /// that must not be compressed like the target of SRV records (RFC 2782)
+    pub fn write_qname_uncompressed(&mut self, qname: &str) -> Result<()> {
+        self.write_labels(qname, false)
+    }
+
+    fn write_labels(&mut self, qname: &str, compress: bool) -> Result<()> {
+        let qname = qname.trim_end_matches('.');
+        let labels: Vec<&str> = if qname.is_empty() {
+            Vec::new()
+        } else {
+            qname.split('.').collect()
+        };
+
+        // Length on the wire, each label with its length byte and the final empty label
+        if labels.iter().map(|l| l.len() + 1).sum::<usize>() + 1 > 255 {
+            return Err(Error::Invalid(format!("Name {} exceeds 255 bytes", qname)));
         }
 
+        for i in 0..labels.len() {
+            let suffix = labels[i..].join(".").to_lowercase();
+            if let Some(&offset) = self.names.get(&suffix).filter(|_| compress) {
+                self.write_u16(0xC000 | offset)?;
+                return Ok(());
+            }
 
-        self.write_u8(0)?;
+            // Pointers only have 14 bits for the offset
+            if self.pos < 0x4000 && !self.names.contains_key(&suffix) {
+                self.names.insert(suffix, self.pos as u16);
+            }
 
-        Ok(())
-    }
-    /*
-    * Better function ->
-    fn write_qname(&mut self, qname: &str) -> Result<()> {
-        for label in qname.split('.') {
-            let len = label.len();
-            if len > 0x3f {
-                return Err("Single label exceeds 63 characters of lenght".into());
+            let label = labels[i];
+            if label.is_empty() {
+                return Err(Error::Invalid(format!("Empty label in name {:?}", qname)));
+            }
+            if label.len() > 0x3f {
+                return Err(Error::Invalid(format!("Label of {} bytes exceeds 63 in name {}", label.len(), qname)));
             }
 
-            self.write_u8(len as u8)?;
-            println!("Scrivi: {len} -> {label}");
+            self.write_u8(label.len() as u8)?;
             for b in label.as_bytes() {
                 self.write_u8(*b)?;
             }
@@ -257,9 +344,17 @@ impl BytePacketBuffer {
 
         Ok(())
     }
-    */
+
+    /// Start writing again from the beginning, forgetting the names written
+    pub fn clear(&mut self) {
+        self.pos = 0;
+        self.names.clear();
+    }
 
     pub fn set(&mut self, pos: usize, val: u8) -> Result<()> {
+        if !self.in_bounds(pos, 1) {
+            return Err(Error::BufferFull);
+        }
         self.buf[pos] = val;
 
         Ok(())
@@ -272,3 +367,34 @@ impl BytePacketBuffer {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_names_are_not_format_errors_when_written() {
+        let long_label = format!("{}.example.com", "a".repeat(64));
+        let long_name = vec!["a".repeat(63); 5].join(".");
+
+        for name in [long_label.as_str(), long_name.as_str(), "a..example.com"] {
+            let mut buffer = BytePacketBuffer::new();
+            match buffer.write_qname(name) {
+                Err(e @ Error::Invalid(_)) => assert!(!e.is_format_error()),
+                other => panic!("{} written as {:?}", name, other),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_names_are_format_errors_when_read() {
+        for data in [&[0x40, b'a', 0][..], &[0xc0, 0x00][..], &[3, b'a', b'b'][..]] {
+            let mut buffer = BytePacketBuffer::with_size(data.len());
+            buffer.buf.copy_from_slice(data);
+
+            let mut name = String::new();
+            let e = buffer.read_qname(&mut name).unwrap_err();
+            assert!(e.is_format_error(), "{:?} read as {:?}", data, e);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+use crate::dns_packet::DnsPacket;
+use crate::dns_record::DnsRecord;
+use crate::query_class::QueryClass;
+use crate::query_type::QueryType;
+use crate::result_code::ResultCode;
+
+/* == Cache == */
+
+/// Name, record type and class of a cached answer.
+/// A missing record type stands for all of them, used for NXDOMAIN
+/// which applies to the name regardless of the type (RFC 2308)
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub name: String,
+    pub qtype: Option<QueryType>,
+    pub class: QueryClass
+}
+
+#[derive(Clone, Debug)]
+struct CacheEntry {
+    rescode: ResultCode,
+    answers: Vec<DnsRecord>,
+    authorities: Vec<DnsRecord>, // SOA record of negative answers, with any NSEC proofs
+    stored_at: Instant, // TTLs of the records are relative to this moment
+    ttl: u32, // lifetime of the entry, lowest TTL of the set
+    last_used: u64 // tick of the last access, used for the LRU eviction
+}
+
+/// Answers of previous lookups, kept for as long as their TTLs allow
+#[derive(Debug)]
+pub struct Cache {
+    entries: HashMap<CacheKey, CacheEntry>,
+    lru: BTreeMap<u64, CacheKey>, // entries ordered from least to most recently used
+    capacity: usize,
+    tick: u64
+}
+
+impl Cache {
+    pub fn new(capacity: usize) -> Cache {
+        Cache {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            capacity,
+            tick: 0
+        }
+    }
+
+    /// Get the answer stored for a name, with TTLs reduced by the time
+    /// they have spent in the cache. Expired entries are removed.
+    ///
+    /// Cached name errors are returned for every record type of the name.
+    pub fn lookup(&mut self, name: &str, qtype: QueryType, class: QueryClass) -> Option<DnsPacket> {
+        let name = name.to_lowercase();
+
+        self.lookup_key(CacheKey { name: name.clone(), qtype: Some(qtype), class })
+            .or_else(|| self.lookup_key(CacheKey { name, qtype: None, class }))
+    }
+
+    fn lookup_key(&mut self, key: CacheKey) -> Option<DnsPacket> {
+        let entry = self.entries.get(&key)?;
+        let elapsed = entry.stored_at.elapsed().as_secs();
+        if elapsed >= entry.ttl as u64 {
+            self.remove(&key);
+            return None;
+        }
+
+        let tick = self.next_tick();
+        let entry = self.entries.get_mut(&key)?;
+
+        // Mark the entry as the most recently used
+        self.lru.remove(&entry.last_used);
+        self.lru.insert(tick, key);
+        entry.last_used = tick;
+
+        let age = |records: &[DnsRecord]| -> Vec<DnsRecord> {
+            records
+                .iter()
+                .map(|record| {
+                    let mut record = record.clone();
+                    record.set_ttl(record.get_ttl().saturating_sub(elapsed as u32));
+                    record
+                })
+                .collect()
+        };
+
+        let mut packet = DnsPacket::new();
+        packet.header.rescode = entry.rescode;
+        packet.answers = age(&entry.answers);
+        packet.authorities = age(&entry.authorities);
+
+        Some(packet)
+    }
+
+    /// Store a set of records for a name, replacing what was there before.
+    /// Sets with a TTL of zero are never cached.
+    pub fn store(&mut self, name: &str, qtype: QueryType, class: QueryClass, records: Vec<DnsRecord>) {
+        let ttl = match records.iter().map(|r| r.get_ttl()).min() {
+            Some(x) => x,
+            None => return,
+        };
+
+        let key = CacheKey {
+            name: name.to_lowercase(),
+            qtype: Some(qtype),
+            class
+        };
+        self.insert(key, ResultCode::NOERROR, records, Vec::new(), ttl);
+    }
+
+    /// Store a negative answer (RFC 2308): NXDOMAIN for the whole name, or
+    /// NODATA (NOERROR without answers) for a single record type.
+    /// The answer lives for the lower of the TTL and the minimum field of the
+    /// SOA record from the authority section, without one it isn't cached.
+    pub fn store_negative(&mut self, name: &str, qtype: QueryType, class: QueryClass,
+                          rescode: ResultCode, authorities: &[DnsRecord]) {
+        let mut soa = match authorities.iter().find(|r| matches!(r, DnsRecord::SOA { .. })) {
+            Some(x) => x.clone(),
+            None => return,
+        };
+
+        let ttl = match soa {
+            DnsRecord::SOA { minimum, ttl, .. } => ttl.min(minimum),
+            _ => return,
+        };
+        soa.set_ttl(ttl);
+
+        let qtype = match rescode {
+            ResultCode::NXDOMAIN => None,
+            ResultCode::NOERROR => Some(qtype),
+            _ => return,
+        };
+
+        // Keep the DNSSEC proofs of the denial with the SOA, for clients validating it
+        let mut records = vec![soa];
+        for record in authorities {
+            if matches!(record.get_querytype(), QueryType::NSEC | QueryType::NSEC3 | QueryType::RRSIG) {
+                let mut record = record.clone();
+                record.set_ttl(record.get_ttl().min(ttl));
+                records.push(record);
+            }
+        }
+
+        let key = CacheKey {
+            name: name.to_lowercase(),
+            qtype,
+            class
+        };
+        self.insert(key, rescode, Vec::new(), records, ttl);
+    }
+
+    fn insert(&mut self, key: CacheKey, rescode: ResultCode, answers: Vec<DnsRecord>,
+              authorities: Vec<DnsRecord>, ttl: u32) {
+        if self.capacity == 0 || ttl == 0 {
+            return;
+        }
+
+        self.remove(&key);
+
+        // Make room by dropping expired entries first, then the least recently used
+        if self.entries.len() >= self.capacity {
+            self.evict_expired();
+        }
+        while self.entries.len() >= self.capacity {
+            let oldest = match self.lru.values().next() {
+                Some(x) => x.clone(),
+                None => break,
+            };
+            self.remove(&oldest);
+        }
+
+        let tick = self.next_tick();
+        self.lru.insert(tick, key.clone());
+        self.entries.insert(key, CacheEntry {
+            rescode,
+            answers,
+            authorities,
+            stored_at: Instant::now(),
+            ttl,
+            last_used: tick
+        });
+    }
+
+    /// Remove every entry whose lifetime is over
+    pub fn evict_expired(&mut self) {
+        let expired: Vec<CacheKey> = self.entries
+            .iter()
+            .filter(|(_, entry)| entry.stored_at.elapsed().as_secs() >= entry.ttl as u64)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in expired {
+            self.remove(&key);
+        }
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.last_used);
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
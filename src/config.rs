@@ -0,0 +1,113 @@
+use std::env::Args;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use crate::resolver::RetryPolicy;
+use crate::error::{Error, Result};
+
+/* == ServerConfig == */
+/// Settings of the server, read from the command line
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub port: u16, // port the server listens on
+    pub root_hints: Option<String>, // path of the root hints file,
+    // the built-in table is used when missing
+    pub cache_size: usize, // maximum number of cached answers
+    pub tcp_idle_timeout: u64, // seconds before an idle TCP connection is closed
+    pub tcp_connections: usize, // TCP connections served at once, more are closed right away
+    pub hosts: Option<String>, // path of the local data, in the format of /etc/hosts
+    pub version: Option<String>, // answer to CHAOS version.bind queries
+    pub identity: Option<String>, // answer to CHAOS hostname.bind and id.server queries
+    pub workers: usize, // threads answering UDP queries
+    pub queue_size: usize, // UDP queries waiting for a worker, more are answered with SERVFAIL
+    pub upstreams: Vec<SocketAddr>, // servers to forward the queries to,
+    // they are resolved from the root servers when empty
+    pub upstream_timeout: u64, // milliseconds to wait for a server before asking again
+    pub retries: u32, // times a server is asked again, waiting twice as long every time
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig::new()
+    }
+}
+
+impl ServerConfig {
+    pub fn new() -> ServerConfig {
+        ServerConfig {
+            port: 2053,
+            root_hints: None,
+            cache_size: 10000,
+            tcp_idle_timeout: 10,
+            tcp_connections: 64,
+            hosts: None,
+            version: Some(format!("dns-server {}", env!("CARGO_PKG_VERSION"))),
+            identity: None,
+            workers: 8,
+            queue_size: 128,
+            upstreams: Vec::new(),
+            upstream_timeout: 1500,
+            retries: 1,
+        }
+    }
+
+    /// Read the configuration from the command line arguments
+    ///
+    /// Usage: dns-server [--port PORT] [--root-hints FILE] [--cache-size N]
+    ///                   [--tcp-idle-timeout SECONDS] [--tcp-connections N] [--hosts FILE]
+    ///                   [--version-string TEXT] [--identity TEXT]
+    ///                   [--workers N] [--queue-size N]
+    ///                   [--upstream ADDR[:PORT]]... [--upstream-timeout MS] [--retries N]
+    ///
+    /// An empty version string or identity keeps it from being answered
+    pub fn from_args(args: Args) -> Result<ServerConfig> {
+        let mut config = ServerConfig::new();
+        let mut args = args.skip(1);
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| Error::Invalid(format!("Missing value for {}", arg)));
+
+            match arg.as_str() {
+                "--port" => config.port = value()?.parse()?,
+                "--root-hints" => config.root_hints = Some(value()?),
+                "--cache-size" => config.cache_size = value()?.parse()?,
+                "--tcp-idle-timeout" => {
+                    config.tcp_idle_timeout = value()?.parse()?;
+                    if config.tcp_idle_timeout == 0 {
+                        return Err(Error::Invalid("--tcp-idle-timeout must be at least 1 second".to_string()));
+                    }
+                }
+                "--tcp-connections" => config.tcp_connections = value()?.parse()?,
+                "--hosts" => config.hosts = Some(value()?),
+                "--version-string" => config.version = Some(value()?).filter(|x| !x.is_empty()),
+                "--identity" => config.identity = Some(value()?).filter(|x| !x.is_empty()),
+                "--workers" => config.workers = value()?.parse()?,
+                "--queue-size" => config.queue_size = value()?.parse()?,
+                "--upstream" => config.upstreams.push(parse_upstream(&value()?)?),
+                "--upstream-timeout" => config.upstream_timeout = value()?.parse()?,
+                "--retries" => config.retries = value()?.parse()?,
+                _ => return Err(Error::Invalid(format!("Unknown argument: {}", arg))),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Timeout and retries of the queries to other servers
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            timeout: Duration::from_millis(self.upstream_timeout),
+            retries: self.retries
+        }
+    }
+}
+
+/// Address of an upstream server, on port 53 unless given
+/// Example: 192.0.2.53, 192.0.2.53:5353 or [2001:db8::53]:53
+fn parse_upstream(value: &str) -> Result<SocketAddr> {
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    let ip = value.parse::<IpAddr>()?;
+    Ok(SocketAddr::new(ip, 53))
+}
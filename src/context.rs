@@ -0,0 +1,44 @@
+use std::sync::Mutex;
+use crate::cache::Cache;
+use crate::config::ServerConfig;
+use crate::hosts::Hosts;
+use crate::random;
+use crate::root_hints::RootHints;
+use crate::upstream::Upstreams;
+use crate::error::Result;
+
+/* == ServerContext == */
+/// State shared by every query handled by the server
+pub struct ServerContext {
+    pub config: ServerConfig,
+    pub hints: RootHints,
+    pub hosts: Hosts,
+    pub upstreams: Upstreams,
+    pub cache: Mutex<Cache>
+}
+
+impl ServerContext {
+    pub fn new(config: ServerConfig) -> Result<ServerContext> {
+        // Every query to another server needs random numbers
+        random::check()?;
+
+        let hints = match config.root_hints {
+            Some(ref path) => RootHints::from_file(path)?,
+            None => RootHints::new(),
+        };
+        let hosts = match config.hosts {
+            Some(ref path) => Hosts::from_file(path)?,
+            None => Hosts::new(),
+        };
+        let upstreams = Upstreams::new(config.upstreams.clone());
+        let cache = Mutex::new(Cache::new(config.cache_size));
+
+        Ok(ServerContext {
+            config,
+            hints,
+            hosts,
+            upstreams,
+            cache
+        })
+    }
+}
@@ -1,9 +1,8 @@
 use crate::byte_packet_buffer::BytePacketBuffer;
 use crate::result_code::ResultCode;
+use crate::error::Result;
 
 
-type Error = Box<dyn std::error::Error>;
-type Result<T> = std::result::Result<T, Error>;
 
 /* == DnsHeader == */
 
@@ -20,7 +19,8 @@ pub struct DnsHeader {
     pub opcode: u8, // tipically always 0
     pub response: bool, // 0 for queries, 1 for responses
 
-    pub rescode: ResultCode, // set by server to indicate status of response
+    pub rescode: ResultCode, // set by server to indicate status of response,
+    // only the lower four bits are written, the rest goes into the OPT record
     pub checking_disabled: bool,
     pub authed_data: bool,
     pub z: bool, // reserved
@@ -34,6 +34,12 @@ pub struct DnsHeader {
     pub resource_entries: u16
 }
 
+impl Default for DnsHeader {
+    fn default() -> Self {
+        DnsHeader::new()
+    }
+}
+
 impl DnsHeader {
     pub fn new() -> DnsHeader {
         DnsHeader {
@@ -70,7 +76,7 @@ impl DnsHeader {
         self.opcode = (a >> 3) & 0x0F;
         self.response = (a & (1 << 7)) > 0;
 
-        self.rescode = ResultCode::from_num(b & 0x0F);
+        self.rescode = ResultCode::from_num((b & 0x0F) as u16);
         self.checking_disabled = (b & (1 << 4)) > 0;
         self.authed_data = (b & (1 << 5)) > 0;
         self.z = (b & (1 << 6)) > 0;
@@ -93,11 +99,11 @@ impl DnsHeader {
                 ((self.truncate_message as u8) << 1) |
                 ((self.authoritative_answer as u8) << 2) |
                 ((self.opcode) << 3) |
-                ((self.response as u8) << 7) as u8
+                ((self.response as u8) << 7)
         )?;
 
         buffer.write_u8(
-            (self.rescode as u8) |
+            ((self.rescode.to_num() & 0x0F) as u8) |
                 ((self.checking_disabled as u8) << 4) |
                 ((self.authed_data as u8) << 5) |
                 ((self.z as u8) << 6) |
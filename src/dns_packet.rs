@@ -1,12 +1,14 @@
 
-use crate::byte_packet_buffer::BytePacketBuffer;
+use crate::byte_packet_buffer::{BytePacketBuffer, UDP_PACKET_SIZE};
 use crate::dns_header::DnsHeader;
 use crate::dns_question::DnsQuestion;
 use crate::dns_record::DnsRecord;
+use crate::query_class::QueryClass;
 use crate::query_type::QueryType;
+use crate::result_code::ResultCode;
+use std::net::Ipv4Addr;
+use crate::error::{Error, Result};
 
-type Error = Box<dyn std::error::Error>;
-type Result<T> = std::result::Result<T, Error>;
 /* == DnsPacket == */
 
 /// Representation of the whole DNS packet
@@ -17,7 +19,14 @@ pub struct DnsPacket {
     pub questions: Vec<DnsQuestion>,
     pub answers: Vec<DnsRecord>,
     pub authorities: Vec<DnsRecord>,
-    pub resources: Vec<DnsRecord>
+    pub resources: Vec<DnsRecord>,
+    pub edns: Option<DnsRecord> // OPT record, kept apart from the resources
+}
+
+impl Default for DnsPacket {
+    fn default() -> Self {
+        DnsPacket::new()
+    }
 }
 
 impl DnsPacket {
@@ -28,6 +37,7 @@ impl DnsPacket {
             answers: Vec::new(),
             authorities: Vec::new(),
             resources: Vec::new(),
+            edns: None,
         }
     }
 
@@ -36,7 +46,7 @@ impl DnsPacket {
         res.header.read(buffer)?;
 
         for _ in 0..res.header.questions {
-            let mut question = DnsQuestion::new("".to_string(), QueryType::UNKNOWN(0));
+            let mut question = DnsQuestion::new("".to_string(), QueryType::UNKNOWN(0), QueryClass::IN);
             question.read(buffer)?;
             res.questions.push(question);
         }
@@ -50,7 +60,15 @@ impl DnsPacket {
         }
         for _ in 0..res.header.resource_entries {
             let rec = DnsRecord::read(buffer)?;
-            res.resources.push(rec);
+            match rec {
+                DnsRecord::OPT { extended_rcode, .. } => {
+                    // Complete the result code with the upper bits
+                    let rescode = ((extended_rcode as u16) << 4) | res.header.rescode.to_num();
+                    res.header.rescode = ResultCode::from_num(rescode);
+                    res.edns = Some(rec);
+                }
+                _ => res.resources.push(rec),
+            }
         }
 
         Ok(res)
@@ -60,7 +78,7 @@ impl DnsPacket {
         self.header.questions = self.questions.len() as u16;
         self.header.answers = self.answers.len() as u16;
         self.header.authoritative_entries = self.authorities.len() as u16;
-        self.header.resource_entries = self.resources.len() as u16;
+        self.header.resource_entries = (self.resources.len() + self.edns.is_some() as usize) as u16;
 
         self.header.write(buffer)?;
 
@@ -76,7 +94,261 @@ impl DnsPacket {
         for r in &self.resources {
             r.write(buffer)?;
         }
+        if let Some(DnsRecord::OPT { packet_len, version, dnssec_ok, ref options, .. }) = self.edns {
+            // The upper bits of the result code are written here
+            let opt = DnsRecord::OPT {
+                packet_len,
+                extended_rcode: (self.header.rescode.to_num() >> 4) as u8,
+                version,
+                dnssec_ok,
+                options: options.clone()
+            };
+            opt.write(buffer)?;
+        }
 
         Ok(())
     }
+
+    /// Write the packet, dropping whole RRsets from the end of the additional,
+    /// authority and answer sections until it fits in the buffer.
+    ///
+    /// The truncated flag is set when answer or authority records had to go,
+    /// missing additional records don't make the answer incomplete (RFC 2181).
+    pub fn write_truncated(&mut self, buffer: &mut BytePacketBuffer) -> Result<()> {
+        loop {
+            buffer.clear();
+            match self.write(buffer) {
+                Ok(()) => return Ok(()),
+                Err(Error::BufferFull) => {},
+                Err(e) => return Err(e),
+            }
+
+            if pop_rrset(&mut self.resources) {
+                continue;
+            }
+            if pop_rrset(&mut self.authorities) || pop_rrset(&mut self.answers) {
+                self.header.truncate_message = true;
+                continue;
+            }
+
+            // Not even the question fits
+            return Err(Error::BufferFull);
+        }
+    }
+
+    /// Add an OPT record advertising 'packet_len' as our UDP payload size
+    pub fn set_edns(&mut self, packet_len: u16, dnssec_ok: bool) {
+        self.edns = Some(DnsRecord::OPT {
+            packet_len,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok,
+            options: Vec::new()
+        });
+    }
+
+    /// EDNS version of the sender, None when it doesn't use EDNS
+    pub fn edns_version(&self) -> Option<u8> {
+        match self.edns {
+            Some(DnsRecord::OPT { version, .. }) => Some(version),
+            _ => None,
+        }
+    }
+
+    /// Whether the sender asked for DNSSEC records with the DO flag
+    pub fn dnssec_ok(&self) -> bool {
+        matches!(self.edns, Some(DnsRecord::OPT { dnssec_ok: true, .. }))
+    }
+
+    /// Largest UDP response the sender accepts, 512 bytes without EDNS
+    pub fn max_udp_size(&self) -> usize {
+        match self.edns {
+            Some(DnsRecord::OPT { packet_len, .. }) => (packet_len as usize).max(UDP_PACKET_SIZE),
+            _ => UDP_PACKET_SIZE,
+        }
+    }
+
+    /// Pick the first A record from the answers
+    pub fn get_first_a(&self) -> Option<Ipv4Addr> {
+        self.answers
+            .iter()
+            .find_map(|record| match record {
+                DnsRecord::A { addr, .. } => Some(*addr),
+                _ => None,
+            })
+    }
+
+    /// Iterate over the name servers in the authority section that are
+    /// responsible for 'qname', as (domain, host) pairs
+    fn get_ns<'a>(&'a self, qname: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.authorities
+            .iter()
+            // Convert the NS records to (domain, host) tuples
+            .filter_map(|record| match record {
+                DnsRecord::NS { domain, host, .. } => Some((domain.as_str(), host.as_str())),
+                _ => None,
+            })
+            // Discard servers which aren't authoritative for our query
+            .filter(move |(domain, _)| is_subdomain(qname, domain))
+    }
+
+    /// Addresses of the name servers for 'qname' found in the glue records
+    /// of the additional section
+    pub fn get_resolved_ns(&self, qname: &str) -> Vec<Ipv4Addr> {
+        self.get_ns(qname)
+            // Look for the matching A records in the additional section
+            .flat_map(|(_, host)| {
+                self.resources
+                    .iter()
+                    .filter_map(move |record| match record {
+                        DnsRecord::A { domain, addr, .. } if domain.eq_ignore_ascii_case(host) => Some(*addr),
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    /// Names of the name servers for 'qname' which came without glue
+    /// records, their addresses have to be resolved separately
+    pub fn get_unresolved_ns<'a>(&'a self, qname: &'a str) -> Vec<&'a str> {
+        self.get_ns(qname)
+            .map(|(_, host)| host)
+            .filter(|host| !self.resources.iter().any(|record| {
+                matches!(record, DnsRecord::A { domain, .. } if domain.eq_ignore_ascii_case(host))
+            }))
+            .collect()
+    }
+
+    /// Zone the name servers in the authority section were delegated for
+    pub fn get_referral_zone<'a>(&'a self, qname: &'a str) -> Option<&'a str> {
+        self.get_ns(qname)
+            .map(|(domain, _)| domain)
+            .next()
+    }
+
+    /// Remove the records a server answering 'qname' for 'zone' (its
+    /// bailiwick) has no business sending, and return them. What is kept:
+    /// - answers for 'qname' and the names its CNAME chain leads to
+    /// - NS, SOA and DS records of the zones above these names, and the
+    ///   NSEC, NSEC3 and RRSIG records proving their nonexistence
+    /// - addresses of the hosts named by the records above
+    ///
+    /// Every name must be within 'zone', otherwise a server could slip in
+    /// records of a domain it is not responsible for (cache poisoning).
+    pub fn scrub(&mut self, qname: &str, zone: &str) -> Vec<DnsRecord> {
+        let mut dropped = Vec::new();
+
+        // The CNAME records may come in any order
+        let mut chain = vec![qname.to_lowercase()];
+        loop {
+            let next = self.answers.iter().find_map(|record| match record {
+                DnsRecord::CNAME { domain, host, .. }
+                    if chain.iter().any(|x| x.eq_ignore_ascii_case(domain))
+                        && !chain.iter().any(|x| x.eq_ignore_ascii_case(host))
+                        && is_subdomain(domain, zone) => Some(host.to_lowercase()),
+                _ => None,
+            });
+            match next {
+                Some(host) => chain.push(host),
+                None => break,
+            }
+        }
+
+        let in_chain = |name: &str| chain.iter().any(|x| x.eq_ignore_ascii_case(name));
+        let (answers, rejected): (Vec<_>, Vec<_>) = self.answers.drain(..)
+            .partition(|record| in_chain(record.get_domain()) && is_subdomain(record.get_domain(), zone));
+        self.answers = answers;
+        dropped.extend(rejected);
+
+        let (authorities, rejected): (Vec<_>, Vec<_>) = self.authorities.drain(..)
+            .partition(|record| {
+                let domain = record.get_domain();
+                let above_chain = chain.iter().any(|x| is_subdomain(x, domain));
+                is_subdomain(domain, zone) && match record.get_querytype() {
+                    QueryType::NS | QueryType::SOA | QueryType::DS => above_chain,
+                    QueryType::NSEC | QueryType::NSEC3 | QueryType::RRSIG => true,
+                    _ => false,
+                }
+            });
+        self.authorities = authorities;
+        dropped.extend(rejected);
+
+        let hosts: Vec<String> = self.answers.iter()
+            .chain(self.authorities.iter())
+            .filter_map(|record| match record {
+                DnsRecord::NS { host, .. } | DnsRecord::MX { host, .. } => Some(host.to_lowercase()),
+                DnsRecord::SRV { target, .. }
+                | DnsRecord::SVCB { target, .. }
+                | DnsRecord::HTTPS { target, .. } => Some(target.to_lowercase()),
+                _ => None,
+            })
+            .collect();
+        let (resources, rejected): (Vec<_>, Vec<_>) = self.resources.drain(..)
+            .partition(|record| {
+                let domain = record.get_domain();
+                matches!(record.get_querytype(), QueryType::A | QueryType::AAAA)
+                    && hosts.iter().any(|x| x.eq_ignore_ascii_case(domain))
+                    && is_subdomain(domain, zone)
+            });
+        self.resources = resources;
+        dropped.extend(rejected);
+
+        dropped
+    }
+}
+
+/// Remove the records of the same name and type as the last one of the section,
+/// false when the section is empty
+fn pop_rrset(records: &mut Vec<DnsRecord>) -> bool {
+    let (domain, qtype) = match records.last() {
+        Some(record) => (record.get_domain().to_string(), record.get_querytype()),
+        None => return false,
+    };
+
+    records.retain(|record| record.get_domain() != domain || record.get_querytype() != qtype);
+
+    true
+}
+
+/// Whether 'name' is equal to or below 'zone'
+pub fn is_subdomain(name: &str, zone: &str) -> bool {
+    let name = name.trim_end_matches('.').as_bytes();
+    let zone = zone.trim_end_matches('.').as_bytes();
+
+    zone.is_empty()
+        || name.eq_ignore_ascii_case(zone)
+        || (name.len() > zone.len()
+            && name[name.len() - zone.len()..].eq_ignore_ascii_case(zone)
+            && name[name.len() - zone.len() - 1] == b'.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write 'packet' and read it back
+    fn round_trip(packet: &mut DnsPacket) -> DnsPacket {
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer).unwrap();
+        buffer.resize(buffer.pos());
+        buffer.seek(0).unwrap();
+        DnsPacket::from_buffer(&mut buffer).unwrap()
+    }
+
+    #[test]
+    fn unknown_result_codes_are_kept() {
+        // NOTAUTH fits in the header
+        let mut packet = DnsPacket::new();
+        packet.header.rescode = ResultCode::from_num(9);
+        assert_eq!(packet.header.rescode, ResultCode::UNKNOWN(9));
+        assert_eq!(round_trip(&mut packet).header.rescode, ResultCode::UNKNOWN(9));
+
+        // BADCOOKIE needs the upper bits of the OPT record
+        packet.set_edns(1232, false);
+        packet.header.rescode = ResultCode::from_num(23);
+        assert_eq!(round_trip(&mut packet).header.rescode, ResultCode::UNKNOWN(23));
+
+        packet.header.rescode = ResultCode::BADVERS;
+        assert_eq!(round_trip(&mut packet).header.rescode, ResultCode::BADVERS);
+    }
 }
@@ -1,30 +1,32 @@
 use crate::byte_packet_buffer::BytePacketBuffer;
+use crate::query_class::QueryClass;
 use crate::query_type::QueryType;
+use crate::error::Result;
 
-type Error = Box<dyn std::error::Error>;
-type Result<T> = std::result::Result<T, Error>;
 /* == DnsQuestion == */
 
-/// Query name (domain) and record type
+/// Query name (domain), record type and class
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DnsQuestion {
     pub name: String,
-    pub qtype: QueryType
+    pub qtype: QueryType,
+    pub qclass: QueryClass
 }
 
 impl DnsQuestion {
-    pub fn new(name: String, qtype: QueryType) -> DnsQuestion {
+    pub fn new(name: String, qtype: QueryType, qclass: QueryClass) -> DnsQuestion {
         DnsQuestion {
             name,
-            qtype
+            qtype,
+            qclass
         }
     }
     // Read the qname from the packet
     pub fn read(&mut self, buffer: &mut BytePacketBuffer) -> Result<()> {
         buffer.read_qname(&mut self.name)?;
         self.qtype = QueryType::from_num(buffer.read_u16()?);
-        let _ = buffer.read_u16()?;
+        self.qclass = QueryClass::from_num(buffer.read_u16()?);
 
         Ok(())
     }
@@ -35,7 +37,7 @@ impl DnsQuestion {
 
         let typenum = self.qtype.to_num();
         buffer.write_u16(typenum)?; // write type number
-        buffer.write_u16(1)?; // ?
+        buffer.write_u16(self.qclass.to_num())?; // write class number
 
         Ok(())
     }
@@ -1,12 +1,21 @@
 
+use std::fmt;
 use std::net::{Ipv4Addr, Ipv6Addr};
 use crate::byte_packet_buffer::BytePacketBuffer;
+use crate::query_class::QueryClass;
 use crate::query_type::QueryType;
+use crate::svc_param::{base64, SvcParam};
+use crate::error::{Error, Result};
 
-type Error = Box<dyn std::error::Error>;
-type Result<T> = std::result::Result<T, Error>;
 /* == DnsRecord == */
 
+/// Option carried in the data of an OPT record
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EdnsOption {
+    pub code: u16,
+    pub data: Vec<u8>
+}
+
 /// Representation of the DNS record
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -15,35 +24,192 @@ pub enum DnsRecord {
     UNKNOWN {
         domain: String,
         qtype: u16,
+        class: QueryClass,
         ttl: u32,
-        data_len: u16
+        data: Vec<u8> // raw RDATA, passed on untouched (RFC 3597)
     },
     A {
         domain: String,
         addr: Ipv4Addr,
+        class: QueryClass,
         ttl: u32
     },
     NS {
         domain: String,
         host: String,
+        class: QueryClass,
         ttl: u32
     },
     CNAME {
         domain: String,
         host: String,
+        class: QueryClass,
+        ttl: u32
+    },
+    PTR {
+        domain: String,
+        host: String,
+        class: QueryClass,
+        ttl: u32
+    },
+    SOA {
+        domain: String,
+        mname: String, // primary name server of the zone
+        rname: String, // mailbox of the administrator
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32, // TTL of negative answers
+        class: QueryClass,
         ttl: u32
     },
     MX {
         domain: String,
         priority: u16,
         host: String,
+        class: QueryClass,
+        ttl: u32
+    },
+    TXT {
+        domain: String,
+        strings: Vec<Vec<u8>>, // character-strings, raw bytes of at most 255 each
+        class: QueryClass,
         ttl: u32
     },
     AAAA {
         domain: String,
         addr: Ipv6Addr,
+        class: QueryClass,
+        ttl: u32
+    },
+    SRV {
+        domain: String,
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String, // host of the service, "." when there is none
+        class: QueryClass,
+        ttl: u32
+    },
+    NAPTR {
+        domain: String,
+        order: u16,
+        preference: u16,
+        flags: Vec<u8>,
+        services: Vec<u8>,
+        regexp: Vec<u8>,
+        replacement: String, // never compressed (RFC 3403)
+        class: QueryClass,
+        ttl: u32
+    },
+    SSHFP {
+        domain: String,
+        algorithm: u8, // of the key: 1 RSA, 2 DSA, 3 ECDSA, 4 Ed25519
+        fp_type: u8, // of the fingerprint: 1 SHA-1, 2 SHA-256
+        fingerprint: Vec<u8>,
+        class: QueryClass,
+        ttl: u32
+    },
+    TLSA {
+        domain: String,
+        usage: u8, // certificate usage: 0 PKIX-TA, 1 PKIX-EE, 2 DANE-TA, 3 DANE-EE
+        selector: u8, // 0 full certificate, 1 public key
+        matching_type: u8, // 0 exact, 1 SHA-256, 2 SHA-512
+        data: Vec<u8>,
+        class: QueryClass,
+        ttl: u32
+    },
+    SVCB {
+        domain: String,
+        priority: u16, // 0 for alias mode
+        target: String, // never compressed (RFC 9460)
+        params: Vec<SvcParam>, // ordered by key
+        class: QueryClass,
+        ttl: u32
+    },
+    HTTPS {
+        domain: String,
+        priority: u16,
+        target: String,
+        params: Vec<SvcParam>,
+        class: QueryClass,
+        ttl: u32
+    },
+    CAA {
+        domain: String,
+        flags: u8, // 128 is the critical flag
+        tag: String, // issue, issuewild, iodef, ... in any case, compare ignoring it
+        value: Vec<u8>,
+        class: QueryClass,
+        ttl: u32
+    },
+    DS {
+        domain: String,
+        key_tag: u16,
+        algorithm: u8,
+        digest_type: u8,
+        digest: Vec<u8>,
+        class: QueryClass,
         ttl: u32
     },
+    RRSIG {
+        domain: String,
+        type_covered: QueryType,
+        algorithm: u8,
+        labels: u8,
+        original_ttl: u32,
+        expiration: u32, // seconds since the epoch
+        inception: u32,
+        key_tag: u16,
+        signer_name: String, // never compressed (RFC 4034)
+        signature: Vec<u8>,
+        class: QueryClass,
+        ttl: u32
+    },
+    NSEC {
+        domain: String,
+        next_domain: String, // never compressed (RFC 4034)
+        types: Vec<QueryType>,
+        class: QueryClass,
+        ttl: u32
+    },
+    DNSKEY {
+        domain: String,
+        flags: u16, // 256 zone key, 257 also secure entry point
+        protocol: u8, // always 3
+        algorithm: u8,
+        public_key: Vec<u8>,
+        class: QueryClass,
+        ttl: u32
+    },
+    NSEC3 {
+        domain: String,
+        hash_algorithm: u8,
+        flags: u8, // 1 is opt-out
+        iterations: u16,
+        salt: Vec<u8>,
+        next_hashed: Vec<u8>, // hash of the next name, binary
+        types: Vec<QueryType>,
+        class: QueryClass,
+        ttl: u32
+    },
+    NSEC3PARAM {
+        domain: String,
+        hash_algorithm: u8,
+        flags: u8,
+        iterations: u16,
+        salt: Vec<u8>,
+        class: QueryClass,
+        ttl: u32
+    },
+    OPT {
+        packet_len: u16, // UDP payload size of the sender, in the class field
+        extended_rcode: u8, // upper eight bits of the result code
+        version: u8,
+        dnssec_ok: bool, // DO flag: sender understands DNSSEC records
+        options: Vec<EdnsOption>
+    },
 }
 
 impl DnsRecord {
@@ -53,9 +219,10 @@ impl DnsRecord {
 
         let qtype_num = buffer.read_u16()?;
         let qtype = QueryType::from_num(qtype_num);
-        let _ = buffer.read_u16()?;
+        let class = QueryClass::from_num(buffer.read_u16()?);
         let ttl = buffer.read_u32()?;
         let data_len = buffer.read_u16()?;
+        let data_start = buffer.pos();
 
         let res = match qtype {
             QueryType::A => {
@@ -69,6 +236,7 @@ impl DnsRecord {
                 DnsRecord::A{
                     domain,
                     addr,
+                    class,
                     ttl
                 }
             }
@@ -79,6 +247,7 @@ impl DnsRecord {
                 DnsRecord::NS {
                     domain,
                     host: ns,
+                    class,
                     ttl
                 }
             }
@@ -89,6 +258,43 @@ impl DnsRecord {
                 DnsRecord::CNAME {
                     domain,
                     host: cname,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::PTR => {
+                let mut ptr = String::new();
+                buffer.read_qname(&mut ptr)?;
+
+                DnsRecord::PTR {
+                    domain,
+                    host: ptr,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::SOA => {
+                let mut mname = String::new();
+                buffer.read_qname(&mut mname)?;
+                let mut rname = String::new();
+                buffer.read_qname(&mut rname)?;
+
+                let serial = buffer.read_u32()?;
+                let refresh = buffer.read_u32()?;
+                let retry = buffer.read_u32()?;
+                let expire = buffer.read_u32()?;
+                let minimum = buffer.read_u32()?;
+
+                DnsRecord::SOA {
+                    domain,
+                    mname,
+                    rname,
+                    serial,
+                    refresh,
+                    retry,
+                    expire,
+                    minimum,
+                    class,
                     ttl
                 }
             }
@@ -101,6 +307,29 @@ impl DnsRecord {
                     domain,
                     priority,
                     host: mx,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::TXT => {
+                let mut strings = Vec::new();
+                let end = buffer.pos() + data_len as usize;
+                while buffer.pos() < end {
+                    let offset = buffer.pos();
+                    let string = buffer.read_character_string()?;
+                    if buffer.pos() > end {
+                        return Err(Error::Malformed {
+                            offset,
+                            reason: "TXT string runs past the record data".to_string()
+                        });
+                    }
+                    strings.push(string);
+                }
+
+                DnsRecord::TXT {
+                    domain,
+                    strings,
+                    class,
                     ttl
                 }
             }
@@ -123,24 +352,429 @@ impl DnsRecord {
                 DnsRecord::AAAA {
                     domain,
                     addr,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::SRV => {
+                let priority = buffer.read_u16()?;
+                let weight = buffer.read_u16()?;
+                let port = buffer.read_u16()?;
+                let mut target = String::new();
+                buffer.read_qname(&mut target)?;
+
+                DnsRecord::SRV {
+                    domain,
+                    priority,
+                    weight,
+                    port,
+                    target,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::NAPTR => {
+                let order = buffer.read_u16()?;
+                let preference = buffer.read_u16()?;
+                let flags = buffer.read_character_string()?;
+                let services = buffer.read_character_string()?;
+                let regexp = buffer.read_character_string()?;
+                let mut replacement = String::new();
+                buffer.read_qname(&mut replacement)?;
+
+                DnsRecord::NAPTR {
+                    domain,
+                    order,
+                    preference,
+                    flags,
+                    services,
+                    regexp,
+                    replacement,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::SSHFP => {
+                let algorithm = buffer.read()?;
+                let fp_type = buffer.read()?;
+                let fingerprint = buffer.read_bytes(data_left(buffer, data_start, data_len)?)?;
+
+                DnsRecord::SSHFP {
+                    domain,
+                    algorithm,
+                    fp_type,
+                    fingerprint,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::TLSA => {
+                let usage = buffer.read()?;
+                let selector = buffer.read()?;
+                let matching_type = buffer.read()?;
+                let data = buffer.read_bytes(data_left(buffer, data_start, data_len)?)?;
+
+                DnsRecord::TLSA {
+                    domain,
+                    usage,
+                    selector,
+                    matching_type,
+                    data,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::SVCB => {
+                let (priority, target, params) = read_svcb_data(buffer, data_len)?;
+
+                DnsRecord::SVCB {
+                    domain,
+                    priority,
+                    target,
+                    params,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::HTTPS => {
+                let (priority, target, params) = read_svcb_data(buffer, data_len)?;
+
+                DnsRecord::HTTPS {
+                    domain,
+                    priority,
+                    target,
+                    params,
+                    class,
                     ttl
                 }
             }
+            QueryType::CAA => {
+                let flags = buffer.read()?;
+                let tag_offset = buffer.pos();
+                let tag = buffer.read_character_string()?;
+                if !is_caa_tag(&tag) {
+                    return Err(Error::Malformed {
+                        offset: tag_offset,
+                        reason: format!("Invalid CAA tag {:?}", String::from_utf8_lossy(&tag))
+                    });
+                }
+                // The value takes the rest of the data
+                let value = buffer.read_bytes(data_left(buffer, data_start, data_len)?)?;
+
+                DnsRecord::CAA {
+                    domain,
+                    flags,
+                    // Kept as received: unlike names, tags aren't made lowercase
+                    // before signing, so changing them breaks the RRSIG
+                    tag: String::from_utf8_lossy(&tag).into_owned(),
+                    value,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::DS => {
+                let key_tag = buffer.read_u16()?;
+                let algorithm = buffer.read()?;
+                let digest_type = buffer.read()?;
+                let digest = buffer.read_bytes(data_left(buffer, data_start, data_len)?)?;
+
+                DnsRecord::DS {
+                    domain,
+                    key_tag,
+                    algorithm,
+                    digest_type,
+                    digest,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::RRSIG => {
+                let type_covered = QueryType::from_num(buffer.read_u16()?);
+                let algorithm = buffer.read()?;
+                let labels = buffer.read()?;
+                let original_ttl = buffer.read_u32()?;
+                let expiration = buffer.read_u32()?;
+                let inception = buffer.read_u32()?;
+                let key_tag = buffer.read_u16()?;
+                let mut signer_name = String::new();
+                buffer.read_qname(&mut signer_name)?;
+                // The signature takes the rest of the data
+                let signature = buffer.read_bytes(data_left(buffer, data_start, data_len)?)?;
+
+                DnsRecord::RRSIG {
+                    domain,
+                    type_covered,
+                    algorithm,
+                    labels,
+                    original_ttl,
+                    expiration,
+                    inception,
+                    key_tag,
+                    signer_name,
+                    signature,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::NSEC => {
+                let end = buffer.pos() + data_len as usize;
+                let mut next_domain = String::new();
+                buffer.read_qname(&mut next_domain)?;
+                let types = read_type_bitmap(buffer, end)?;
+
+                DnsRecord::NSEC {
+                    domain,
+                    next_domain,
+                    types,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::DNSKEY => {
+                let flags = buffer.read_u16()?;
+                let protocol = buffer.read()?;
+                let algorithm = buffer.read()?;
+                let public_key = buffer.read_bytes(data_left(buffer, data_start, data_len)?)?;
+
+                DnsRecord::DNSKEY {
+                    domain,
+                    flags,
+                    protocol,
+                    algorithm,
+                    public_key,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::NSEC3 => {
+                let end = buffer.pos() + data_len as usize;
+                let hash_algorithm = buffer.read()?;
+                let flags = buffer.read()?;
+                let iterations = buffer.read_u16()?;
+                let salt = buffer.read_character_string()?;
+                let next_hashed = buffer.read_character_string()?;
+                let types = read_type_bitmap(buffer, end)?;
+
+                DnsRecord::NSEC3 {
+                    domain,
+                    hash_algorithm,
+                    flags,
+                    iterations,
+                    salt,
+                    next_hashed,
+                    types,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::NSEC3PARAM => {
+                let hash_algorithm = buffer.read()?;
+                let flags = buffer.read()?;
+                let iterations = buffer.read_u16()?;
+                let salt = buffer.read_character_string()?;
+
+                DnsRecord::NSEC3PARAM {
+                    domain,
+                    hash_algorithm,
+                    flags,
+                    iterations,
+                    salt,
+                    class,
+                    ttl
+                }
+            }
+            QueryType::OPT => {
+                // The TTL field holds the extended result code, version and flags
+                let mut options = Vec::new();
+                let end = buffer.pos() + data_len as usize;
+                while buffer.pos() < end {
+                    let code = buffer.read_u16()?;
+                    let len = buffer.read_u16()? as usize;
+                    let data = buffer.read_bytes(len)?;
+
+                    options.push(EdnsOption { code, data });
+                }
+
+                DnsRecord::OPT {
+                    packet_len: class.to_num(),
+                    extended_rcode: (ttl >> 24) as u8,
+                    version: ((ttl >> 16) & 0xFF) as u8,
+                    dnssec_ok: (ttl & 0x8000) > 0,
+                    options
+                }
+            }
             QueryType::UNKNOWN(_) => {
-                buffer.step(data_len as usize)?;
+                let data = buffer.read_bytes(data_len as usize)?;
 
                 DnsRecord::UNKNOWN {
                     domain,
                     qtype: qtype_num,
+                    class,
                     ttl,
-                    data_len
+                    data
                 }
             }
         };
 
+        // The data must end where its length says, otherwise the next
+        // records would be read from the wrong offset
+        let data_read = buffer.pos() - data_start;
+        if data_read != data_len as usize {
+            return Err(Error::Malformed {
+                offset: data_start,
+                reason: format!("{:?} record data of {} bytes, {} announced", qtype, data_read, data_len)
+            });
+        }
+
         Ok(res)
     }
 
+    /// Name the record belongs to, the root for OPT records
+    pub fn get_domain(&self) -> &str {
+        match *self {
+            DnsRecord::UNKNOWN { ref domain, .. }
+            | DnsRecord::A { ref domain, .. }
+            | DnsRecord::NS { ref domain, .. }
+            | DnsRecord::CNAME { ref domain, .. }
+            | DnsRecord::PTR { ref domain, .. }
+            | DnsRecord::SOA { ref domain, .. }
+            | DnsRecord::MX { ref domain, .. }
+            | DnsRecord::TXT { ref domain, .. }
+            | DnsRecord::AAAA { ref domain, .. }
+            | DnsRecord::SRV { ref domain, .. }
+            | DnsRecord::NAPTR { ref domain, .. }
+            | DnsRecord::SSHFP { ref domain, .. }
+            | DnsRecord::TLSA { ref domain, .. }
+            | DnsRecord::SVCB { ref domain, .. }
+            | DnsRecord::HTTPS { ref domain, .. }
+            | DnsRecord::CAA { ref domain, .. }
+            | DnsRecord::DS { ref domain, .. }
+            | DnsRecord::RRSIG { ref domain, .. }
+            | DnsRecord::NSEC { ref domain, .. }
+            | DnsRecord::DNSKEY { ref domain, .. }
+            | DnsRecord::NSEC3 { ref domain, .. }
+            | DnsRecord::NSEC3PARAM { ref domain, .. } => domain,
+            DnsRecord::OPT { .. } => "",
+        }
+    }
+
+    /// Type of the record
+    pub fn get_querytype(&self) -> QueryType {
+        match *self {
+            DnsRecord::UNKNOWN { qtype, .. } => QueryType::UNKNOWN(qtype),
+            DnsRecord::A { .. } => QueryType::A,
+            DnsRecord::NS { .. } => QueryType::NS,
+            DnsRecord::CNAME { .. } => QueryType::CNAME,
+            DnsRecord::PTR { .. } => QueryType::PTR,
+            DnsRecord::SOA { .. } => QueryType::SOA,
+            DnsRecord::MX { .. } => QueryType::MX,
+            DnsRecord::TXT { .. } => QueryType::TXT,
+            DnsRecord::AAAA { .. } => QueryType::AAAA,
+            DnsRecord::SRV { .. } => QueryType::SRV,
+            DnsRecord::NAPTR { .. } => QueryType::NAPTR,
+            DnsRecord::SSHFP { .. } => QueryType::SSHFP,
+            DnsRecord::TLSA { .. } => QueryType::TLSA,
+            DnsRecord::SVCB { .. } => QueryType::SVCB,
+            DnsRecord::HTTPS { .. } => QueryType::HTTPS,
+            DnsRecord::CAA { .. } => QueryType::CAA,
+            DnsRecord::DS { .. } => QueryType::DS,
+            DnsRecord::RRSIG { .. } => QueryType::RRSIG,
+            DnsRecord::NSEC { .. } => QueryType::NSEC,
+            DnsRecord::DNSKEY { .. } => QueryType::DNSKEY,
+            DnsRecord::NSEC3 { .. } => QueryType::NSEC3,
+            DnsRecord::NSEC3PARAM { .. } => QueryType::NSEC3PARAM,
+            DnsRecord::OPT { .. } => QueryType::OPT,
+        }
+    }
+
+    /// Time to live of the record in seconds
+    pub fn get_ttl(&self) -> u32 {
+        match *self {
+            DnsRecord::UNKNOWN { ttl, .. }
+            | DnsRecord::A { ttl, .. }
+            | DnsRecord::NS { ttl, .. }
+            | DnsRecord::CNAME { ttl, .. }
+            | DnsRecord::PTR { ttl, .. }
+            | DnsRecord::SOA { ttl, .. }
+            | DnsRecord::MX { ttl, .. }
+            | DnsRecord::TXT { ttl, .. }
+            | DnsRecord::AAAA { ttl, .. }
+            | DnsRecord::SRV { ttl, .. }
+            | DnsRecord::NAPTR { ttl, .. }
+            | DnsRecord::SSHFP { ttl, .. }
+            | DnsRecord::TLSA { ttl, .. }
+            | DnsRecord::SVCB { ttl, .. }
+            | DnsRecord::HTTPS { ttl, .. }
+            | DnsRecord::CAA { ttl, .. }
+            | DnsRecord::DS { ttl, .. }
+            | DnsRecord::RRSIG { ttl, .. }
+            | DnsRecord::NSEC { ttl, .. }
+            | DnsRecord::DNSKEY { ttl, .. }
+            | DnsRecord::NSEC3 { ttl, .. }
+            | DnsRecord::NSEC3PARAM { ttl, .. } => ttl,
+            DnsRecord::OPT { .. } => 0,
+        }
+    }
+
+    /// Class of the record, meaningless for OPT records whose class field
+    /// holds the UDP payload size
+    pub fn get_class(&self) -> QueryClass {
+        match *self {
+            DnsRecord::UNKNOWN { class, .. }
+            | DnsRecord::A { class, .. }
+            | DnsRecord::NS { class, .. }
+            | DnsRecord::CNAME { class, .. }
+            | DnsRecord::PTR { class, .. }
+            | DnsRecord::SOA { class, .. }
+            | DnsRecord::MX { class, .. }
+            | DnsRecord::TXT { class, .. }
+            | DnsRecord::AAAA { class, .. }
+            | DnsRecord::SRV { class, .. }
+            | DnsRecord::NAPTR { class, .. }
+            | DnsRecord::SSHFP { class, .. }
+            | DnsRecord::TLSA { class, .. }
+            | DnsRecord::SVCB { class, .. }
+            | DnsRecord::HTTPS { class, .. }
+            | DnsRecord::CAA { class, .. }
+            | DnsRecord::DS { class, .. }
+            | DnsRecord::RRSIG { class, .. }
+            | DnsRecord::NSEC { class, .. }
+            | DnsRecord::DNSKEY { class, .. }
+            | DnsRecord::NSEC3 { class, .. }
+            | DnsRecord::NSEC3PARAM { class, .. } => class,
+            DnsRecord::OPT { .. } => QueryClass::UNKNOWN(0),
+        }
+    }
+
+    /// Change the time to live of the record
+    pub fn set_ttl(&mut self, new_ttl: u32) {
+        match *self {
+            DnsRecord::UNKNOWN { ref mut ttl, .. }
+            | DnsRecord::A { ref mut ttl, .. }
+            | DnsRecord::NS { ref mut ttl, .. }
+            | DnsRecord::CNAME { ref mut ttl, .. }
+            | DnsRecord::PTR { ref mut ttl, .. }
+            | DnsRecord::SOA { ref mut ttl, .. }
+            | DnsRecord::MX { ref mut ttl, .. }
+            | DnsRecord::TXT { ref mut ttl, .. }
+            | DnsRecord::AAAA { ref mut ttl, .. }
+            | DnsRecord::SRV { ref mut ttl, .. }
+            | DnsRecord::NAPTR { ref mut ttl, .. }
+            | DnsRecord::SSHFP { ref mut ttl, .. }
+            | DnsRecord::TLSA { ref mut ttl, .. }
+            | DnsRecord::SVCB { ref mut ttl, .. }
+            | DnsRecord::HTTPS { ref mut ttl, .. }
+            | DnsRecord::CAA { ref mut ttl, .. }
+            | DnsRecord::DS { ref mut ttl, .. }
+            | DnsRecord::RRSIG { ref mut ttl, .. }
+            | DnsRecord::NSEC { ref mut ttl, .. }
+            | DnsRecord::DNSKEY { ref mut ttl, .. }
+            | DnsRecord::NSEC3 { ref mut ttl, .. }
+            | DnsRecord::NSEC3PARAM { ref mut ttl, .. } => *ttl = new_ttl,
+            DnsRecord::OPT { .. } => {},
+        }
+    }
+
     pub fn write(&self, buffer: &mut BytePacketBuffer) -> Result<usize> {
         let start_pos = buffer.pos();
 
@@ -148,11 +782,12 @@ impl DnsRecord {
             DnsRecord::A {
                 ref domain,
                 ref addr,
+                class,
                 ttl
             } => {
                 buffer.write_qname(domain)?;
                 buffer.write_u16(QueryType::A.to_num())?;
-                buffer.write_u16(1)?;
+                buffer.write_u16(class.to_num())?;
                 buffer.write_u32(ttl)?;
                 buffer.write_u16(4)?;
 
@@ -165,11 +800,12 @@ impl DnsRecord {
             DnsRecord::NS {
                 ref domain,
                 ref host,
+                class,
                 ttl
             } => {
                 buffer.write_qname(domain)?;
                 buffer.write_u16(QueryType::NS.to_num())?;
-                buffer.write_u16(1)?;
+                buffer.write_u16(class.to_num())?;
                 buffer.write_u32(ttl)?;
 
                 let pos = buffer.pos();
@@ -183,11 +819,12 @@ impl DnsRecord {
             DnsRecord::CNAME {
                 ref domain,
                 ref host,
+                class,
                 ttl
             } => {
                 buffer.write_qname(domain)?;
                 buffer.write_u16(QueryType::CNAME.to_num())?;
-                buffer.write_u16(1)?;
+                buffer.write_u16(class.to_num())?;
                 buffer.write_u32(ttl)?;
 
                 let pos = buffer.pos();
@@ -198,15 +835,66 @@ impl DnsRecord {
                 let size = buffer.pos() - (pos + 2);
                 buffer.set_u16(pos, size as u16)?;
             },
+            DnsRecord::PTR {
+                ref domain,
+                ref host,
+                class,
+                ttl
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::PTR.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_qname(host)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            },
+            DnsRecord::SOA {
+                ref domain,
+                ref mname,
+                ref rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+                class,
+                ttl
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::SOA.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_qname(mname)?;
+                buffer.write_qname(rname)?;
+                buffer.write_u32(serial)?;
+                buffer.write_u32(refresh)?;
+                buffer.write_u32(retry)?;
+                buffer.write_u32(expire)?;
+                buffer.write_u32(minimum)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            },
             DnsRecord::MX {
                 ref domain,
                 priority,
                 ref host,
+                class,
                 ttl
             } => {
                 buffer.write_qname(domain)?;
                 buffer.write_u16(QueryType::MX.to_num())?;
-                buffer.write_u16(1)?;
+                buffer.write_u16(class.to_num())?;
                 buffer.write_u32(ttl)?;
 
                 let pos = buffer.pos();
@@ -218,14 +906,41 @@ impl DnsRecord {
                 let size = buffer.pos() - (pos + 2);
                 buffer.set_u16(pos, size as u16)?;
             },
+            DnsRecord::TXT {
+                ref domain,
+                ref strings,
+                class,
+                ttl
+            } => {
+                // A character-string has a single byte for its length
+                if let Some(s) = strings.iter().find(|s| s.len() > 0xFF) {
+                    return Err(Error::Invalid(format!("TXT string of {} bytes exceeds 255", s.len())));
+                }
+
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::TXT.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                for s in strings {
+                    buffer.write_character_string(s)?;
+                }
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            },
             DnsRecord::AAAA {
                 ref domain,
                 ref addr,
+                class,
                 ttl
             } => {
                 buffer.write_qname(domain)?;
                 buffer.write_u16(QueryType::AAAA.to_num())?;
-                buffer.write_u16(1)?;
+                buffer.write_u16(class.to_num())?;
                 buffer.write_u32(ttl)?;
                 buffer.write_u16(16)?;
 
@@ -233,11 +948,850 @@ impl DnsRecord {
                     buffer.write_u16(*octet)?;
                 }
             },
-            DnsRecord::UNKNOWN { .. } => {
-                println!("Skipping record: {:?}", self);
+            DnsRecord::SRV {
+                ref domain,
+                priority,
+                weight,
+                port,
+                ref target,
+                class,
+                ttl
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::SRV.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_u16(priority)?;
+                buffer.write_u16(weight)?;
+                buffer.write_u16(port)?;
+                buffer.write_qname_uncompressed(target)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            },
+            DnsRecord::NAPTR {
+                ref domain,
+                order,
+                preference,
+                ref flags,
+                ref services,
+                ref regexp,
+                ref replacement,
+                class,
+                ttl
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::NAPTR.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_u16(order)?;
+                buffer.write_u16(preference)?;
+                buffer.write_character_string(flags)?;
+                buffer.write_character_string(services)?;
+                buffer.write_character_string(regexp)?;
+                buffer.write_qname_uncompressed(replacement)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            },
+            DnsRecord::SSHFP {
+                ref domain,
+                algorithm,
+                fp_type,
+                ref fingerprint,
+                class,
+                ttl
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::SSHFP.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+                buffer.write_u16(2 + fingerprint.len() as u16)?;
+
+                buffer.write_u8(algorithm)?;
+                buffer.write_u8(fp_type)?;
+                buffer.write_bytes(fingerprint)?;
+            },
+            DnsRecord::TLSA {
+                ref domain,
+                usage,
+                selector,
+                matching_type,
+                ref data,
+                class,
+                ttl
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::TLSA.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+                buffer.write_u16(3 + data.len() as u16)?;
+
+                buffer.write_u8(usage)?;
+                buffer.write_u8(selector)?;
+                buffer.write_u8(matching_type)?;
+                buffer.write_bytes(data)?;
+            },
+            DnsRecord::SVCB {
+                ref domain,
+                priority,
+                ref target,
+                ref params,
+                class,
+                ttl
+            }
+            | DnsRecord::HTTPS {
+                ref domain,
+                priority,
+                ref target,
+                ref params,
+                class,
+                ttl
+            } => {
+                // Keys must be in strictly increasing order
+                let mut params: Vec<&SvcParam> = params.iter().collect();
+                params.sort_by_key(|p| p.key());
+                if params.windows(2).any(|w| w[0].key() == w[1].key()) {
+                    return Err(Error::Invalid("Duplicate SvcParam key".to_string()));
+                }
+
+                buffer.write_qname(domain)?;
+                buffer.write_u16(self.get_querytype().to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_u16(priority)?;
+                buffer.write_qname_uncompressed(target)?;
+                for param in params {
+                    param.write(buffer)?;
+                }
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            },
+            DnsRecord::CAA {
+                ref domain,
+                flags,
+                ref tag,
+                ref value,
+                class,
+                ttl
+            } => {
+                if !is_caa_tag(tag.as_bytes()) {
+                    return Err(Error::Invalid(format!("Invalid CAA tag: {:?}", tag)));
+                }
+
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::CAA.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+                buffer.write_u16((2 + tag.len() + value.len()) as u16)?;
+
+                buffer.write_u8(flags)?;
+                buffer.write_character_string(tag.as_bytes())?;
+                buffer.write_bytes(value)?;
+            },
+            DnsRecord::DS {
+                ref domain,
+                key_tag,
+                algorithm,
+                digest_type,
+                ref digest,
+                class,
+                ttl
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::DS.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+                buffer.write_u16(4 + digest.len() as u16)?;
+
+                buffer.write_u16(key_tag)?;
+                buffer.write_u8(algorithm)?;
+                buffer.write_u8(digest_type)?;
+                buffer.write_bytes(digest)?;
+            },
+            DnsRecord::RRSIG {
+                ref domain,
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                expiration,
+                inception,
+                key_tag,
+                ref signer_name,
+                ref signature,
+                class,
+                ttl
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::RRSIG.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_u16(type_covered.to_num())?;
+                buffer.write_u8(algorithm)?;
+                buffer.write_u8(labels)?;
+                buffer.write_u32(original_ttl)?;
+                buffer.write_u32(expiration)?;
+                buffer.write_u32(inception)?;
+                buffer.write_u16(key_tag)?;
+                buffer.write_qname_uncompressed(signer_name)?;
+                buffer.write_bytes(signature)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            },
+            DnsRecord::NSEC {
+                ref domain,
+                ref next_domain,
+                ref types,
+                class,
+                ttl
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::NSEC.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_qname_uncompressed(next_domain)?;
+                write_type_bitmap(buffer, types)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            },
+            DnsRecord::DNSKEY {
+                ref domain,
+                flags,
+                protocol,
+                algorithm,
+                ref public_key,
+                class,
+                ttl
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::DNSKEY.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+                buffer.write_u16(4 + public_key.len() as u16)?;
+
+                buffer.write_u16(flags)?;
+                buffer.write_u8(protocol)?;
+                buffer.write_u8(algorithm)?;
+                buffer.write_bytes(public_key)?;
+            },
+            DnsRecord::NSEC3 {
+                ref domain,
+                hash_algorithm,
+                flags,
+                iterations,
+                ref salt,
+                ref next_hashed,
+                ref types,
+                class,
+                ttl
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::NSEC3.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_u8(hash_algorithm)?;
+                buffer.write_u8(flags)?;
+                buffer.write_u16(iterations)?;
+                buffer.write_character_string(salt)?;
+                buffer.write_character_string(next_hashed)?;
+                write_type_bitmap(buffer, types)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            },
+            DnsRecord::NSEC3PARAM {
+                ref domain,
+                hash_algorithm,
+                flags,
+                iterations,
+                ref salt,
+                class,
+                ttl
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::NSEC3PARAM.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                buffer.write_u8(hash_algorithm)?;
+                buffer.write_u8(flags)?;
+                buffer.write_u16(iterations)?;
+                buffer.write_character_string(salt)?;
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            },
+            DnsRecord::OPT {
+                packet_len,
+                extended_rcode,
+                version,
+                dnssec_ok,
+                ref options
+            } => {
+                buffer.write_u8(0)?; // root domain
+                buffer.write_u16(QueryType::OPT.to_num())?;
+                buffer.write_u16(packet_len)?;
+                buffer.write_u8(extended_rcode)?;
+                buffer.write_u8(version)?;
+                buffer.write_u16((dnssec_ok as u16) << 15)?;
+
+                let pos = buffer.pos();
+                buffer.write_u16(0)?;
+
+                for option in options {
+                    buffer.write_u16(option.code)?;
+                    buffer.write_u16(option.data.len() as u16)?;
+                    buffer.write_bytes(&option.data)?;
+                }
+
+                let size = buffer.pos() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            },
+            DnsRecord::UNKNOWN {
+                ref domain,
+                qtype,
+                class,
+                ttl,
+                ref data
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(qtype)?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+                buffer.write_u16(data.len() as u16)?;
+                buffer.write_bytes(data)?;
             },
         }
 
         Ok(buffer.pos() - start_pos)
     }
 }
+
+/// Bytes left of the record data starting at 'data_start', for the field
+/// taking the rest of it. The fields before it must not have run past the end.
+fn data_left(buffer: &BytePacketBuffer, data_start: usize, data_len: u16) -> Result<usize> {
+    (data_start + data_len as usize).checked_sub(buffer.pos()).ok_or_else(|| Error::Malformed {
+        offset: data_start,
+        reason: format!("Record data longer than the {} bytes announced", data_len)
+    })
+}
+
+/// Whether 'tag' is a valid CAA tag: 1 to 15 letters and digits (RFC 8659)
+fn is_caa_tag(tag: &[u8]) -> bool {
+    !tag.is_empty() && tag.len() <= 15 && tag.iter().all(|b| b.is_ascii_alphanumeric())
+}
+
+/// Read the data shared by SVCB and HTTPS records: priority, target and
+/// the parameters, whose keys must be in strictly increasing order
+fn read_svcb_data(buffer: &mut BytePacketBuffer, data_len: u16) -> Result<(u16, String, Vec<SvcParam>)> {
+    let end = buffer.pos() + data_len as usize;
+
+    let priority = buffer.read_u16()?;
+    let mut target = String::new();
+    buffer.read_qname(&mut target)?;
+
+    let mut params: Vec<SvcParam> = Vec::new();
+    while buffer.pos() < end {
+        let key = buffer.read_u16()?;
+        let len = buffer.read_u16()? as usize;
+
+        if params.last().is_some_and(|p| p.key() >= key) {
+            return Err(Error::Malformed {
+                offset: buffer.pos() - 4,
+                reason: format!("SvcParam key{} out of order", key)
+            });
+        }
+        params.push(SvcParam::read(buffer, key, len)?);
+    }
+
+    Ok((priority, target, params))
+}
+
+/// Read the type bitmap of NSEC and NSEC3 records, up to 'end'
+///
+/// Types are grouped in windows of 256: a window number, the length of the
+/// bitmap and the bitmap, with the most significant bit standing for the
+/// lowest type (RFC 4034 4.1.2)
+fn read_type_bitmap(buffer: &mut BytePacketBuffer, end: usize) -> Result<Vec<QueryType>> {
+    let mut types = Vec::new();
+
+    while buffer.pos() < end {
+        let window = buffer.read()? as u16;
+        let len = buffer.read()? as usize;
+        if len == 0 || len > 32 {
+            return Err(Error::Malformed {
+                offset: buffer.pos() - 1,
+                reason: format!("Invalid type bitmap length {}", len)
+            });
+        }
+
+        let bitmap = buffer.read_bytes(len)?;
+        for (i, byte) in bitmap.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    types.push(QueryType::from_num((window << 8) | (i * 8 + bit) as u16));
+                }
+            }
+        }
+    }
+
+    Ok(types)
+}
+
+/// Write the types as a type bitmap, in increasing order and without duplicates
+fn write_type_bitmap(buffer: &mut BytePacketBuffer, types: &[QueryType]) -> Result<()> {
+    let mut nums: Vec<u16> = types.iter().map(|t| t.to_num()).collect();
+    nums.sort_unstable();
+    nums.dedup();
+
+    let mut i = 0;
+    while i < nums.len() {
+        let window = nums[i] >> 8;
+        let mut bitmap = [0u8; 32];
+        let mut len = 0;
+
+        while i < nums.len() && nums[i] >> 8 == window {
+            let low = (nums[i] & 0xFF) as usize;
+            bitmap[low / 8] |= 0x80 >> (low % 8);
+            len = low / 8 + 1;
+            i += 1;
+        }
+
+        buffer.write_u8(window as u8)?;
+        buffer.write_u8(len as u8)?;
+        buffer.write_bytes(&bitmap[0..len])?;
+    }
+
+    Ok(())
+}
+
+/// Salt of NSEC3 records in hexadecimal, "-" when empty
+fn salt_hex(salt: &[u8]) -> String {
+    if salt.is_empty() {
+        "-".to_string()
+    } else {
+        hex(salt)
+    }
+}
+
+/// Base32 encoding with the extended hex alphabet and no padding,
+/// the presentation of the hashed names of NSEC3 records (RFC 5155)
+fn base32hex(bytes: &[u8]) -> String {
+    const CHARS: &[u8] = b"0123456789abcdefghijklmnopqrstuv";
+
+    let mut res = String::new();
+    let mut bits = 0u32;
+    let mut count = 0;
+    for &b in bytes {
+        bits = (bits << 8) | b as u32;
+        count += 8;
+        while count >= 5 {
+            count -= 5;
+            res.push(CHARS[((bits >> count) & 0x1F) as usize] as char);
+        }
+    }
+    if count > 0 {
+        res.push(CHARS[((bits << (5 - count)) & 0x1F) as usize] as char);
+    }
+    res
+}
+
+/// Name with the trailing dot of the presentation format
+fn fqdn(name: &str) -> String {
+    format!("{}.", name.trim_end_matches('.'))
+}
+
+/// Bytes in hexadecimal, two digits each
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Character-string in quotes, bytes outside of printable ASCII as \DDD
+fn quoted(bytes: &[u8]) -> String {
+    let mut res = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => {
+                res.push('\\');
+                res.push(b as char);
+            }
+            0x20..=0x7E => res.push(b as char),
+            _ => res.push_str(&format!("\\{:03}", b)),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// Records in the presentation format of zone files.
+/// Unknown types use the generic format of RFC 3597: TYPE999 \# 3 010203
+impl fmt::Display for DnsRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let DnsRecord::OPT { packet_len, version, dnssec_ok, ref options, .. } = *self {
+            return write!(f, "; EDNS: version {}, udp {}, do {}, {} options",
+                          version, packet_len, dnssec_ok, options.len());
+        }
+
+        write!(f, "{} {} {} {} ", fqdn(self.get_domain()), self.get_ttl(), self.get_class(),
+               self.get_querytype())?;
+
+        match *self {
+            DnsRecord::A { ref addr, .. } => write!(f, "{}", addr),
+            DnsRecord::AAAA { ref addr, .. } => write!(f, "{}", addr),
+            DnsRecord::NS { ref host, .. }
+            | DnsRecord::CNAME { ref host, .. }
+            | DnsRecord::PTR { ref host, .. } => write!(f, "{}", fqdn(host)),
+            DnsRecord::SOA { ref mname, ref rname, serial, refresh, retry, expire, minimum, .. } => {
+                write!(f, "{} {} {} {} {} {} {}", fqdn(mname), fqdn(rname),
+                       serial, refresh, retry, expire, minimum)
+            }
+            DnsRecord::MX { priority, ref host, .. } => write!(f, "{} {}", priority, fqdn(host)),
+            DnsRecord::SRV { priority, weight, port, ref target, .. } => {
+                write!(f, "{} {} {} {}", priority, weight, port, fqdn(target))
+            }
+            DnsRecord::NAPTR { order, preference, ref flags, ref services, ref regexp,
+                               ref replacement, .. } => {
+                write!(f, "{} {} {} {} {} {}", order, preference, quoted(flags), quoted(services),
+                       quoted(regexp), fqdn(replacement))
+            }
+            DnsRecord::SSHFP { algorithm, fp_type, ref fingerprint, .. } => {
+                write!(f, "{} {} {}", algorithm, fp_type, hex(fingerprint))
+            }
+            DnsRecord::TLSA { usage, selector, matching_type, ref data, .. } => {
+                write!(f, "{} {} {} {}", usage, selector, matching_type, hex(data))
+            }
+            DnsRecord::SVCB { priority, ref target, ref params, .. }
+            | DnsRecord::HTTPS { priority, ref target, ref params, .. } => {
+                write!(f, "{} {}", priority, fqdn(target))?;
+                for param in params {
+                    write!(f, " {}", param)?;
+                }
+                Ok(())
+            }
+            DnsRecord::DS { key_tag, algorithm, digest_type, ref digest, .. } => {
+                write!(f, "{} {} {} {}", key_tag, algorithm, digest_type, hex(digest))
+            }
+            DnsRecord::RRSIG { type_covered, algorithm, labels, original_ttl, expiration, inception,
+                               key_tag, ref signer_name, ref signature, .. } => {
+                write!(f, "{} {} {} {} {} {} {} {} {}", type_covered, algorithm, labels, original_ttl,
+                       expiration, inception, key_tag, fqdn(signer_name), base64(signature))
+            }
+            DnsRecord::NSEC { ref next_domain, ref types, .. } => {
+                write!(f, "{}", fqdn(next_domain))?;
+                for t in types {
+                    write!(f, " {}", t)?;
+                }
+                Ok(())
+            }
+            DnsRecord::DNSKEY { flags, protocol, algorithm, ref public_key, .. } => {
+                write!(f, "{} {} {} {}", flags, protocol, algorithm, base64(public_key))
+            }
+            DnsRecord::NSEC3 { hash_algorithm, flags, iterations, ref salt, ref next_hashed,
+                               ref types, .. } => {
+                write!(f, "{} {} {} {} {}", hash_algorithm, flags, iterations, salt_hex(salt),
+                       base32hex(next_hashed))?;
+                for t in types {
+                    write!(f, " {}", t)?;
+                }
+                Ok(())
+            }
+            DnsRecord::NSEC3PARAM { hash_algorithm, flags, iterations, ref salt, .. } => {
+                write!(f, "{} {} {} {}", hash_algorithm, flags, iterations, salt_hex(salt))
+            }
+            DnsRecord::CAA { flags, ref tag, ref value, .. } => {
+                write!(f, "{} {} {}", flags, tag, quoted(value))
+            }
+            DnsRecord::TXT { ref strings, .. } => {
+                let strings: Vec<String> = strings.iter().map(|s| quoted(s)).collect();
+                write!(f, "{}", strings.join(" "))
+            }
+            DnsRecord::UNKNOWN { ref data, .. } => {
+                write!(f, "\\# {}", data.len())?;
+                if !data.is_empty() {
+                    write!(f, " {}", hex(data))?;
+                }
+                Ok(())
+            }
+            DnsRecord::OPT { .. } => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write 'record' and read it back
+    fn round_trip(record: &DnsRecord) -> DnsRecord {
+        let mut buffer = BytePacketBuffer::new();
+        let len = record.write(&mut buffer).unwrap();
+        assert_eq!(len, buffer.pos());
+
+        buffer.seek(0).unwrap();
+        let read = DnsRecord::read(&mut buffer).unwrap();
+        assert_eq!(buffer.pos(), len);
+        read
+    }
+
+    /// Record of 'qtype' for example.com with 'rdata', announcing 'rdlength' bytes
+    fn raw_record(qtype: QueryType, rdlength: u16, rdata: &[u8]) -> BytePacketBuffer {
+        let mut buffer = BytePacketBuffer::new();
+        buffer.write_qname("example.com").unwrap();
+        buffer.write_u16(qtype.to_num()).unwrap();
+        buffer.write_u16(QueryClass::IN.to_num()).unwrap();
+        buffer.write_u32(300).unwrap();
+        buffer.write_u16(rdlength).unwrap();
+        buffer.write_bytes(rdata).unwrap();
+        // Followed by the start of another record
+        buffer.write_bytes(&[0xc0, 0x0c, 0x00, 0x01]).unwrap();
+
+        buffer.resize(buffer.pos());
+        buffer.seek(0).unwrap();
+        buffer
+    }
+
+    /// Read a record which must be rejected as malformed, which may also
+    /// be noticed as reading past the end of the message
+    fn assert_malformed(qtype: QueryType, rdlength: u16, rdata: &[u8]) {
+        let mut buffer = raw_record(qtype, rdlength, rdata);
+        match DnsRecord::read(&mut buffer) {
+            Err(e) if e.is_format_error() => {}
+            other => panic!("{:?} with {:?} read as {:?}", qtype, rdata, other),
+        }
+    }
+
+    #[test]
+    fn sshfp_round_trip() {
+        let record = DnsRecord::SSHFP {
+            domain: "host.example.com".to_string(),
+            algorithm: 4,
+            fp_type: 2,
+            fingerprint: (0..32).collect(),
+            class: QueryClass::IN,
+            ttl: 300
+        };
+        assert_eq!(round_trip(&record), record);
+    }
+
+    #[test]
+    fn tlsa_round_trip() {
+        let record = DnsRecord::TLSA {
+            domain: "_443._tcp.example.com".to_string(),
+            usage: 3,
+            selector: 1,
+            matching_type: 1,
+            data: (0..32).rev().collect(),
+            class: QueryClass::IN,
+            ttl: 300
+        };
+        assert_eq!(round_trip(&record), record);
+    }
+
+    #[test]
+    fn caa_round_trip() {
+        let record = DnsRecord::CAA {
+            domain: "example.com".to_string(),
+            flags: 128,
+            tag: "issue".to_string(),
+            value: b"letsencrypt.org".to_vec(),
+            class: QueryClass::IN,
+            ttl: 300
+        };
+        assert_eq!(round_trip(&record), record);
+    }
+
+    #[test]
+    fn naptr_round_trip() {
+        let record = DnsRecord::NAPTR {
+            domain: "example.com".to_string(),
+            order: 100,
+            preference: 10,
+            flags: b"u".to_vec(),
+            services: b"E2U+sip".to_vec(),
+            regexp: b"!^.*$!sip:info@example.com!".to_vec(),
+            replacement: "".to_string(),
+            class: QueryClass::IN,
+            ttl: 300
+        };
+        assert_eq!(round_trip(&record), record);
+
+        let record = DnsRecord::NAPTR {
+            domain: "example.com".to_string(),
+            order: 100,
+            preference: 20,
+            flags: b"s".to_vec(),
+            services: b"SIP+D2U".to_vec(),
+            regexp: Vec::new(),
+            replacement: "_sip._udp.example.com".to_string(),
+            class: QueryClass::IN,
+            ttl: 300
+        };
+        assert_eq!(round_trip(&record), record);
+    }
+
+    #[test]
+    fn short_rdata_is_rejected() {
+        // The fixed fields alone don't fit in what RDLENGTH announces
+        assert_malformed(QueryType::SSHFP, 1, &[4]);
+        assert_malformed(QueryType::TLSA, 2, &[3, 1]);
+        assert_malformed(QueryType::CAA, 1, &[0]);
+        assert_malformed(QueryType::NAPTR, 4, &[0, 100, 0, 10]);
+    }
+
+    #[test]
+    fn long_rdata_is_rejected() {
+        // NAPTR data doesn't end with the replacement
+        assert_malformed(QueryType::NAPTR, 11, &[0, 1, 0, 2, 0, 0, 0, 0, 0, 0xff, 0xff]);
+        // A character-string running past RDLENGTH
+        assert_malformed(QueryType::CAA, 4, &[0, 5, b'i', b's', b's', b'u', b'e']);
+    }
+
+    #[test]
+    fn caa_tag_case_is_kept() {
+        let mut buffer = raw_record(QueryType::CAA, 11, b"\x00\x05IsSuEca.test");
+        match DnsRecord::read(&mut buffer).unwrap() {
+            DnsRecord::CAA { ref tag, .. } => assert_eq!(tag, "IsSuE"),
+            other => panic!("read as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn invalid_caa_tag_is_rejected() {
+        assert_malformed(QueryType::CAA, 2, &[0, 0]);
+        assert_malformed(QueryType::CAA, 9, b"\x00\x07issue-x");
+        assert_malformed(QueryType::CAA, 6, b"\x00\x04\xffbad");
+    }
+
+    #[test]
+    fn invalid_caa_tag_is_not_written() {
+        let record = DnsRecord::CAA {
+            domain: "example.com".to_string(),
+            flags: 0,
+            tag: "issue-x".to_string(),
+            value: Vec::new(),
+            class: QueryClass::IN,
+            ttl: 300
+        };
+        let mut buffer = BytePacketBuffer::new();
+        assert!(matches!(record.write(&mut buffer), Err(Error::Invalid(_))));
+    }
+
+    #[test]
+    fn dnssec_round_trip() {
+        let records = [
+            DnsRecord::DS {
+                domain: "example.com".to_string(),
+                key_tag: 12345,
+                algorithm: 13,
+                digest_type: 2,
+                digest: (0..32).collect(),
+                class: QueryClass::IN,
+                ttl: 300
+            },
+            DnsRecord::RRSIG {
+                domain: "example.com".to_string(),
+                type_covered: QueryType::A,
+                algorithm: 13,
+                labels: 2,
+                original_ttl: 300,
+                expiration: 1_900_000_000,
+                inception: 1_800_000_000,
+                key_tag: 12345,
+                signer_name: "example.com".to_string(),
+                signature: (0..64).collect(),
+                class: QueryClass::IN,
+                ttl: 300
+            },
+            DnsRecord::DNSKEY {
+                domain: "example.com".to_string(),
+                flags: 257,
+                protocol: 3,
+                algorithm: 13,
+                public_key: (0..64).rev().collect(),
+                class: QueryClass::IN,
+                ttl: 300
+            },
+            DnsRecord::NSEC {
+                domain: "a.example.com".to_string(),
+                next_domain: "c.example.com".to_string(),
+                types: vec![QueryType::A, QueryType::MX, QueryType::RRSIG, QueryType::NSEC, QueryType::CAA],
+                class: QueryClass::IN,
+                ttl: 300
+            },
+            DnsRecord::NSEC3 {
+                domain: "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom.example.com".to_string(),
+                hash_algorithm: 1,
+                flags: 1,
+                iterations: 10,
+                salt: vec![0xaa, 0xbb, 0xcc, 0xdd],
+                next_hashed: (0..20).collect(),
+                types: vec![QueryType::NS, QueryType::SOA, QueryType::DNSKEY, QueryType::NSEC3PARAM],
+                class: QueryClass::IN,
+                ttl: 300
+            },
+            DnsRecord::NSEC3PARAM {
+                domain: "example.com".to_string(),
+                hash_algorithm: 1,
+                flags: 0,
+                iterations: 0,
+                salt: Vec::new(),
+                class: QueryClass::IN,
+                ttl: 0
+            },
+        ];
+
+        for record in &records {
+            assert_eq!(&round_trip(record), record);
+        }
+    }
+
+    #[test]
+    fn short_dnssec_rdata_is_rejected() {
+        // Fixed fields longer than RDLENGTH, and nothing left for the
+        // digest, key or signature
+        assert_malformed(QueryType::DS, 3, &[0x30, 0x39, 13, 2]);
+        assert_malformed(QueryType::DNSKEY, 2, &[1, 1, 3, 13]);
+
+        // Signer name running past RDLENGTH
+        let mut rrsig = vec![0, 1, 13, 2, 0, 0, 1, 44, 0x71, 0x3f, 0xb3, 0, 0x6b, 0x49, 0xd2, 0, 0x30, 0x39];
+        rrsig.extend_from_slice(b"\x07example\x03com\x00");
+        assert_malformed(QueryType::RRSIG, 20, &rrsig);
+    }
+
+    #[test]
+    fn invalid_type_bitmap_is_rejected() {
+        assert_malformed(QueryType::NSEC, 5, &[0, 0, 0, 0, 0]);
+        assert_malformed(QueryType::NSEC, 35, &[0, 0, 33, 0xff]);
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+use std::io;
+use std::net::AddrParseError;
+use std::num::ParseIntError;
+
+/* == Error == */
+/// Everything that can go wrong in the server.
+/// Errors found while parsing a message carry the offset where parsing
+/// failed; they mean the message is malformed, not that the server failed.
+
+#[derive(Debug)]
+pub enum Error {
+    Truncated { offset: usize }, // the message ends before the data it announces
+    BadLabel { offset: usize }, // label that is neither a length nor a pointer, or empty
+    PointerLoop { offset: usize }, // too many compression pointers followed
+    NameTooLong { offset: usize }, // names are 255 bytes at most
+    Malformed { offset: usize, reason: String }, // any other invalid data in a message
+    BufferFull, // the message doesn't fit the buffer, the caller may truncate it
+    Invalid(String), // data that can't be written, e.g. a label over 63 bytes, or an invalid setting
+    Io(io::Error),
+    Timeout, // no response in time
+    Upstream(String), // no server gave a usable answer
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Whether the error comes from a malformed message, answered with FORMERR.
+    /// Every other error is a failure of the server, answered with SERVFAIL.
+    pub fn is_format_error(&self) -> bool {
+        self.offset().is_some()
+    }
+
+    /// Offset in the message where parsing failed
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            Error::Truncated { offset }
+            | Error::BadLabel { offset }
+            | Error::PointerLoop { offset }
+            | Error::NameTooLong { offset }
+            | Error::Malformed { offset, .. } => Some(offset),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Truncated { offset } => write!(f, "Message truncated at offset {}", offset),
+            Error::BadLabel { offset } => write!(f, "Invalid label at offset {}", offset),
+            Error::PointerLoop { offset } => write!(f, "Too many compression pointers at offset {}", offset),
+            Error::NameTooLong { offset } => write!(f, "Name exceeds 255 bytes at offset {}", offset),
+            Error::Malformed { offset, ref reason } => write!(f, "{} at offset {}", reason, offset),
+            Error::BufferFull => write!(f, "Buffer full"),
+            Error::Invalid(ref reason) => write!(f, "{}", reason),
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Timeout => write!(f, "Timed out"),
+            Error::Upstream(ref reason) => write!(f, "Upstream failure: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Read timeouts surface as WouldBlock or TimedOut depending on the platform
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Error::Timeout,
+            _ => Error::Io(e),
+        }
+    }
+}
+
+impl From<ParseIntError> for Error {
+    fn from(e: ParseIntError) -> Error {
+        Error::Invalid(e.to_string())
+    }
+}
+
+impl From<AddrParseError> for Error {
+    fn from(e: AddrParseError) -> Error {
+        Error::Invalid(e.to_string())
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use crate::dns_packet::DnsPacket;
+use crate::dns_record::DnsRecord;
+use crate::query_class::QueryClass;
+use crate::query_type::QueryType;
+use crate::reverse_name::{ip_to_name, name_to_ip};
+use crate::error::{Error, Result};
+
+/* == Hosts == */
+
+/// TTL of the records answered from local data
+const LOCAL_TTL: u32 = 300;
+
+/// Local data of the server: names and their addresses, answered without
+/// any lookup. Reverse lookups of the addresses are answered from the same
+/// data, so no reverse zone has to be maintained by hand.
+#[derive(Clone, Debug, Default)]
+pub struct Hosts {
+    addresses: HashMap<String, Vec<IpAddr>>, // name -> addresses
+    names: HashMap<IpAddr, Vec<String>> // address -> names, first is the canonical one
+}
+
+impl Hosts {
+    pub fn new() -> Hosts {
+        Hosts::default()
+    }
+
+    /// Load the local data from a file in the format of '/etc/hosts'
+    ///
+    /// Example line: 10.0.0.5  build.lab.example  build
+    pub fn from_file(path: &str) -> Result<Hosts> {
+        let content = fs::read_to_string(path)?;
+        Hosts::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<Hosts> {
+        let mut hosts = Hosts::new();
+
+        for (n, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let addr = fields.next().unwrap_or("").parse::<IpAddr>()
+                .map_err(|e| Error::Invalid(format!("Invalid address on hosts line {}: {}", n + 1, e)))?;
+
+            for name in fields {
+                hosts.add(name, addr);
+            }
+        }
+
+        Ok(hosts)
+    }
+
+    /// Add an address for a name
+    pub fn add(&mut self, name: &str, addr: IpAddr) {
+        let name = name.trim_end_matches('.').to_lowercase();
+
+        let addresses = self.addresses.entry(name.clone()).or_default();
+        if !addresses.contains(&addr) {
+            addresses.push(addr);
+        }
+
+        let names = self.names.entry(addr).or_default();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    /// Answer a query from the local data, None when the name isn't part of it.
+    /// PTR answers are synthesized for the reverse names of the addresses.
+    pub fn answer(&self, qname: &str, qtype: QueryType) -> Option<DnsPacket> {
+        let qname = qname.trim_end_matches('.').to_lowercase();
+        let mut packet = DnsPacket::new();
+        packet.header.authoritative_answer = true;
+
+        if let Some(addresses) = self.addresses.get(&qname) {
+            for addr in addresses {
+                match (addr, qtype) {
+                    (IpAddr::V4(addr), QueryType::A) => packet.answers.push(DnsRecord::A {
+                        domain: qname.clone(),
+                        addr: *addr,
+                        class: QueryClass::IN,
+                        ttl: LOCAL_TTL
+                    }),
+                    (IpAddr::V6(addr), QueryType::AAAA) => packet.answers.push(DnsRecord::AAAA {
+                        domain: qname.clone(),
+                        addr: *addr,
+                        class: QueryClass::IN,
+                        ttl: LOCAL_TTL
+                    }),
+                    _ => {},
+                }
+            }
+
+            // A name without records of the type is answered with NODATA
+            return Some(packet);
+        }
+
+        let addr = name_to_ip(&qname)?;
+        let names = self.names.get(&addr)?;
+        if qtype == QueryType::PTR {
+            for name in names {
+                packet.answers.push(DnsRecord::PTR {
+                    domain: ip_to_name(&addr),
+                    host: name.clone(),
+                    class: QueryClass::IN,
+                    ttl: LOCAL_TTL
+                });
+            }
+        }
+
+        Some(packet)
+    }
+}
@@ -0,0 +1,48 @@
+#![allow(clippy::upper_case_acronyms, clippy::identity_op)]
+
+//! DNS message handling, a recursive resolver and the server built on them.
+//!
+//! Messages are built and parsed with `DnsPacket` over a `BytePacketBuffer`,
+//! `resolver` queries other servers and `server` answers queries with the
+//! settings and state of a `ServerContext`. Nothing is printed unless a
+//! function receiving the messages is installed with `log::set_logger`.
+
+/* == Messages == */
+pub mod log;
+pub mod error;
+pub mod byte_packet_buffer;
+pub mod result_code;
+pub mod dns_header;
+pub mod query_type;
+pub mod query_class;
+pub mod dns_question;
+pub mod dns_record;
+pub mod dns_packet;
+pub mod svc_param;
+pub mod reverse_name;
+pub mod random;
+
+/* == Resolver == */
+pub mod root_hints;
+pub mod resolver;
+pub mod upstream;
+
+/* == Server == */
+pub mod config;
+pub mod cache;
+pub mod context;
+pub mod hosts;
+pub mod tcp;
+pub mod server;
+
+pub use crate::byte_packet_buffer::BytePacketBuffer;
+pub use crate::config::ServerConfig;
+pub use crate::context::ServerContext;
+pub use crate::dns_header::DnsHeader;
+pub use crate::dns_packet::DnsPacket;
+pub use crate::dns_question::DnsQuestion;
+pub use crate::dns_record::DnsRecord;
+pub use crate::error::{Error, Result};
+pub use crate::query_class::QueryClass;
+pub use crate::query_type::QueryType;
+pub use crate::result_code::ResultCode;
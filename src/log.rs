@@ -0,0 +1,48 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+/* == Log == */
+// The library doesn't print anything by itself: what happens while
+// resolving and serving queries goes to the hook installed by the program
+// using it, and nowhere when there is none.
+
+/// How much a message matters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Info, // queries, answers and lookups as they happen
+    Warn // failures and anything ignored or dropped
+}
+
+/// Function receiving the messages
+pub type Logger = fn(Level, fmt::Arguments);
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Install the function receiving the messages, once for the whole program.
+/// False when one was installed already.
+pub fn set_logger(logger: Logger) -> bool {
+    LOGGER.set(logger).is_ok()
+}
+
+/// Pass a message to the installed function, if any
+pub fn log(level: Level, args: fmt::Arguments) {
+    if let Some(logger) = LOGGER.get() {
+        logger(level, args);
+    }
+}
+
+/// Log a message of level Info, formatted like 'println!'
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Info, format_args!($($arg)*))
+    };
+}
+
+/// Log a message of level Warn, formatted like 'println!'
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+pub(crate) use {log_info, log_warn};
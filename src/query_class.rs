@@ -0,0 +1,61 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+/* == QueryClass == */
+/// Class of a question or record, nearly always IN
+
+#[derive(PartialEq, Eq, Debug, Clone, Hash, Copy)]
+pub enum QueryClass {
+    UNKNOWN(u16),
+    IN, /// Internet
+    CH, /// Chaos: used for queries about the server itself, e.g. version.bind
+    HS, /// Hesiod
+    NONE, /// No class, used by dynamic updates (RFC 2136)
+    ANY, // Any class, only found in questions
+}
+
+impl QueryClass {
+    pub fn to_num(self) -> u16 {
+        match self {
+            QueryClass::UNKNOWN(x) => x,
+            QueryClass::IN => 1,
+            QueryClass::CH => 3,
+            QueryClass::HS => 4,
+            QueryClass::NONE => 254,
+            QueryClass::ANY => 255,
+        }
+    }
+
+    pub fn from_num(num: u16) -> QueryClass {
+        match num {
+            1 => QueryClass::IN,
+            3 => QueryClass::CH,
+            4 => QueryClass::HS,
+            254 => QueryClass::NONE,
+            255 => QueryClass::ANY,
+            _ => QueryClass::UNKNOWN(num)
+        }
+    }
+}
+
+impl Ord for QueryClass {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_num().cmp(&other.to_num())
+    }
+}
+
+impl PartialOrd for QueryClass {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Mnemonic of the class, CLASS followed by the number for unknown ones
+impl fmt::Display for QueryClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            QueryClass::UNKNOWN(x) => write!(f, "CLASS{}", x),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use std::cmp::Ordering;
+use std::fmt;
+
 
 /* == QueryType == */
 /// Record type being queried, used for DNS question
@@ -8,19 +11,53 @@ pub enum QueryType {
     A, /// Alias: map name to IP
     NS, /// Name server: address of the DNS server for a domain
     CNAME, /// Canonical name: maps names to names
+    SOA, /// Start of authority: zone parameters, used for negative caching
+    PTR, /// Pointer: maps an address, written as a name under in-addr.arpa, to a name
     MX, /// Main eXchange: the host of the email server for a domain
-    AAAA, // /// IPv6 alias
+    TXT, /// Text: arbitrary strings, e.g. for service discovery
+    AAAA, /// IPv6 alias
+    SRV, /// Service: host and port of a service, looked up as _service._proto.name
+    NAPTR, /// Naming authority pointer: rewrite rules, e.g. from phone numbers to SIP URIs
+    DS, /// Delegation signer: digest of the DNSKEY of a child zone
+    SSHFP, /// SSH fingerprint: fingerprint of the SSH host key
+    RRSIG, /// Signature of a set of records
+    NSEC, /// Next secure: next name of the zone and types of this one, proves non-existence
+    DNSKEY, /// Public key of a zone
+    NSEC3, /// Hashed version of NSEC
+    NSEC3PARAM, /// Parameters of the NSEC3 hashing of a zone
+    TLSA, /// TLS association: certificate or public key of a TLS server (DANE)
+    SVCB, /// Service binding: endpoints and parameters of a service
+    HTTPS, /// Service binding for HTTPS, e.g. supported protocols and address hints
+    CAA, /// Certification authority authorization: CAs allowed to issue certificates
+    OPT, // EDNS(0) pseudo-record, only found in the additional section
 }
 
 impl QueryType {
-    pub fn to_num(&self) -> u16 {
-        match *self {
+    pub fn to_num(self) -> u16 {
+        match self {
             QueryType::UNKNOWN(x) => x,
             QueryType::A => 1,
             QueryType::NS => 2,
             QueryType::CNAME => 5,
+            QueryType::SOA => 6,
+            QueryType::PTR => 12,
             QueryType::MX => 15,
+            QueryType::TXT => 16,
             QueryType::AAAA => 28,
+            QueryType::SRV => 33,
+            QueryType::NAPTR => 35,
+            QueryType::DS => 43,
+            QueryType::SSHFP => 44,
+            QueryType::RRSIG => 46,
+            QueryType::NSEC => 47,
+            QueryType::DNSKEY => 48,
+            QueryType::NSEC3 => 50,
+            QueryType::NSEC3PARAM => 51,
+            QueryType::TLSA => 52,
+            QueryType::SVCB => 64,
+            QueryType::HTTPS => 65,
+            QueryType::CAA => 257,
+            QueryType::OPT => 41,
         }
     }
 
@@ -29,9 +66,49 @@ impl QueryType {
             1 => QueryType::A,
             2 => QueryType::NS,
             5 => QueryType::CNAME,
+            6 => QueryType::SOA,
+            12 => QueryType::PTR,
             15 => QueryType::MX,
+            16 => QueryType::TXT,
             28 => QueryType::AAAA,
+            33 => QueryType::SRV,
+            35 => QueryType::NAPTR,
+            43 => QueryType::DS,
+            44 => QueryType::SSHFP,
+            46 => QueryType::RRSIG,
+            47 => QueryType::NSEC,
+            48 => QueryType::DNSKEY,
+            50 => QueryType::NSEC3,
+            51 => QueryType::NSEC3PARAM,
+            52 => QueryType::TLSA,
+            64 => QueryType::SVCB,
+            65 => QueryType::HTTPS,
+            257 => QueryType::CAA,
+            41 => QueryType::OPT,
             _ => QueryType::UNKNOWN(num)
         }
     }
 }
+
+/// Types are ordered by their number, as in the type bitmaps of NSEC records
+impl Ord for QueryType {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_num().cmp(&other.to_num())
+    }
+}
+
+impl PartialOrd for QueryType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Mnemonic of the type, TYPE followed by the number for unknown ones
+impl fmt::Display for QueryType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            QueryType::UNKNOWN(x) => write!(f, "TYPE{}", x),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+#[cfg(unix)]
+use std::fs::File;
+#[cfg(unix)]
+use std::io::Read;
+#[cfg(unix)]
+use std::sync::OnceLock;
+#[cfg(not(unix))]
+use crate::error::Error;
+use crate::error::Result;
+
+/* == Random == */
+// Ids and source ports of our queries must be unpredictable, otherwise
+// anyone can forge the responses (cache poisoning). The randomness comes
+// from the kernel's generator, which is suitable for cryptography. Only
+// Unix systems are supported, elsewhere the server refuses to start.
+
+#[cfg(unix)]
+static URANDOM: OnceLock<File> = OnceLock::new();
+
+/// Fill 'buf' with random bytes
+#[cfg(unix)]
+pub fn fill(buf: &mut [u8]) -> Result<()> {
+    let file = match URANDOM.get() {
+        Some(x) => x,
+        None => {
+            let file = File::open("/dev/urandom")?;
+            URANDOM.get_or_init(|| file)
+        }
+    };
+
+    // Reads go through a shared reference, so no lock is needed
+    let mut file: &File = file;
+    file.read_exact(buf)?;
+
+    Ok(())
+}
+
+/// Fill 'buf' with random bytes, there is no source of them here
+#[cfg(not(unix))]
+pub fn fill(_buf: &mut [u8]) -> Result<()> {
+    Err(Error::Invalid("No random number generator on this platform".to_string()))
+}
+
+/// Check that random numbers can be had, before any query needs them
+pub fn check() -> Result<()> {
+    fill(&mut [0u8; 2])
+}
+
+/// Random 16 bit number, e.g. the id of a query
+pub fn random_u16() -> Result<u16> {
+    let mut buf = [0u8; 2];
+    fill(&mut buf)?;
+    Ok(u16::from_be_bytes(buf))
+}
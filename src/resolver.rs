@@ -0,0 +1,338 @@
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+use crate::byte_packet_buffer::{BytePacketBuffer, MAX_PACKET_SIZE};
+use crate::dns_packet::DnsPacket;
+use crate::dns_question::DnsQuestion;
+use crate::query_class::QueryClass;
+use crate::query_type::QueryType;
+use crate::random::random_u16;
+use crate::result_code::ResultCode;
+use crate::root_hints::RootHints;
+use crate::tcp;
+use crate::log::{log_info, log_warn};
+use crate::error::{Error, Result};
+
+/* == Resolver == */
+
+/// Maximum number of referrals followed for a single name
+const MAX_REFERRALS: usize = 16;
+/// Maximum nesting of lookups for the addresses of name servers
+const MAX_NS_DEPTH: usize = 4;
+/// Time allowed for connecting to and reading from a server over TCP
+const TCP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// UDP payload size we advertise to the servers we query
+const EDNS_PAYLOAD_SIZE: u16 = 4096;
+/// Random source ports are picked above the well-known ones
+const MIN_SOURCE_PORT: u16 = 1024;
+/// Random source ports tried before leaving the choice to the system
+const SOURCE_PORT_ATTEMPTS: usize = 10;
+
+/// How long to wait for a server to answer over UDP, and how many times to
+/// ask it again. The wait doubles with every attempt.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub timeout: Duration, // wait for the first attempt
+    pub retries: u32 // attempts after the first one
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            timeout: Duration::from_millis(1500),
+            retries: 1
+        }
+    }
+}
+
+/// Build our query packet, its id is set when it's sent.
+/// We do the recursion ourselves unless 'recursion_desired' is set,
+/// when forwarding the query to a recursive server.
+/// The DO flag is set, so DNSSEC records come along with the answers.
+fn build_query(qname: &str, qtype: QueryType, recursion_desired: bool) -> DnsPacket {
+    let mut packet = DnsPacket::new();
+
+    packet.header.questions = 1;
+    packet.header.recursion_desired = recursion_desired;
+    packet.questions.push(DnsQuestion::new(qname.to_string(), qtype, QueryClass::IN));
+    packet.set_edns(EDNS_PAYLOAD_SIZE, true);
+
+    packet
+}
+
+/// Send a single query to 'server' and wait for the response.
+/// Servers that don't understand EDNS are asked again without it,
+/// truncated responses are retried over TCP.
+pub fn lookup(qname: &str, qtype: QueryType, server: SocketAddr, recursion_desired: bool,
+              policy: &RetryPolicy) -> Result<DnsPacket> {
+    let mut query = build_query(qname, qtype, recursion_desired);
+    let mut response = lookup_udp(&mut query, server, policy)?;
+
+    if response.edns_version().is_none()
+        && matches!(response.header.rescode, ResultCode::FORMERR | ResultCode::NOTIMP) {
+        log_info!("No EDNS support on {}, retrying without it", server);
+        query.edns = None;
+        response = lookup_udp(&mut query, server, policy)?;
+    }
+
+    if response.header.truncate_message {
+        log_info!("Truncated response from {}, retrying over TCP", server);
+        return lookup_tcp(&mut query, server);
+    }
+
+    Ok(response)
+}
+
+/// Bind a UDP socket on a random port, for sending a query to 'server'
+fn bind_random_port(server: SocketAddr) -> Result<UdpSocket> {
+    let mut local: SocketAddr = match server {
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+    };
+
+    for _ in 0..SOURCE_PORT_ATTEMPTS {
+        local.set_port(MIN_SOURCE_PORT + random_u16()? % (u16::MAX - MIN_SOURCE_PORT));
+        match UdpSocket::bind(local) {
+            Ok(socket) => return Ok(socket),
+            Err(e) if e.kind() == ErrorKind::AddrInUse => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    // Every port tried is taken, the system's choice is random enough
+    local.set_port(0);
+    Ok(UdpSocket::bind(local)?)
+}
+
+/// Whether 'response' answers 'query': a response with the same id and
+/// the same question. Servers rejecting the query may leave the question out.
+fn answers_query(response: &DnsPacket, query: &DnsPacket) -> bool {
+    if !response.header.response || response.header.id != query.header.id {
+        return false;
+    }
+
+    match (response.questions.as_slice(), query.questions.as_slice()) {
+        ([r], [q]) => r.name.eq_ignore_ascii_case(&q.name) && r.qtype == q.qtype && r.qclass == q.qclass,
+        ([], _) => matches!(response.header.rescode, ResultCode::FORMERR | ResultCode::NOTIMP),
+        _ => false,
+    }
+}
+
+/// Send a query over UDP, again when no response arrives in time.
+/// Every call uses a new random id and source port, and only a response
+/// from 'server' answering the query is accepted, anything else is ignored.
+pub fn lookup_udp(query: &mut DnsPacket, server: SocketAddr, policy: &RetryPolicy)
+    -> Result<DnsPacket> {
+    let socket = bind_random_port(server)?;
+    query.header.id = random_u16()?;
+
+    // Write packet to a buffer
+    let mut req_buffer = BytePacketBuffer::new();
+    query.write(&mut req_buffer)?;
+    let data = req_buffer.get_range(0, req_buffer.pos())?;
+
+    let mut timeout = policy.timeout.max(Duration::from_millis(1));
+    for attempt in 0..=policy.retries {
+        if attempt > 0 {
+            log_info!("No response from {}, attempt {} of {}", server, attempt + 1, policy.retries + 1);
+        }
+
+        // Send it to the server using our socket:
+        socket.send_to(data, server)?;
+        let deadline = Instant::now() + timeout;
+
+        // Wait until the deadline, whatever is ignored on the way
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()).filter(|x| !x.is_zero()) {
+            socket.set_read_timeout(Some(remaining))?;
+
+            // To prepare for receiving the response, we'll create a new `BytePacketBuffer`,
+            // and ask the socket to write the response directly into our buffer.
+            let mut res_buffer = BytePacketBuffer::with_size(MAX_PACKET_SIZE);
+            let (len, src) = match socket.recv_from(&mut res_buffer.buf).map_err(Error::from) {
+                Ok(x) => x,
+                Err(Error::Timeout) => break,
+                Err(e) => return Err(e),
+            };
+            res_buffer.resize(len);
+
+            if src != server {
+                log_warn!("Ignoring response from {}, the query went to {}", src, server);
+                continue;
+            }
+
+            // `DnsPacket::from_buffer()` is then used to actually parse the
+            // packet. Anyone can forge the source address, so a datagram that
+            // doesn't parse is ignored like any other one not answering the query.
+            let response = match parse_response(&mut res_buffer, server) {
+                Ok(x) => x,
+                Err(e) => {
+                    log_warn!("Ignoring response: {}", e);
+                    continue;
+                }
+            };
+            if !answers_query(&response, query) {
+                log_warn!("Ignoring response from {} not matching the query", server);
+                continue;
+            }
+
+            return Ok(response);
+        }
+
+        timeout *= 2;
+    }
+
+    Err(Error::Timeout)
+}
+
+/// Send a query over TCP, framed with the length of the message
+pub fn lookup_tcp(query: &mut DnsPacket, server: SocketAddr) -> Result<DnsPacket> {
+    let mut stream = TcpStream::connect_timeout(&server, TCP_TIMEOUT)?;
+    stream.set_read_timeout(Some(TCP_TIMEOUT))?;
+    query.header.id = random_u16()?;
+
+    let mut req_buffer = BytePacketBuffer::with_size(MAX_PACKET_SIZE);
+    query.write(&mut req_buffer)?;
+    let len = req_buffer.pos();
+    tcp::write_message(&mut stream, &req_buffer, len)?;
+
+    let mut res_buffer = tcp::read_message(&mut stream)?;
+    let response = parse_response(&mut res_buffer, server)?;
+    if !answers_query(&response, query) {
+        return Err(Error::Upstream(format!("Response from {} not matching the query", server)));
+    }
+
+    Ok(response)
+}
+
+/// Parse the response of 'server'. A malformed response is a failure of
+/// that server, not of the query we are answering.
+fn parse_response(buffer: &mut BytePacketBuffer, server: SocketAddr) -> Result<DnsPacket> {
+    DnsPacket::from_buffer(buffer).map_err(|e| match e {
+        e if e.is_format_error() => Error::Upstream(format!("Malformed response from {}: {}", server, e)),
+        e => e,
+    })
+}
+
+/// Drop the records of a response from 'server' that fall outside 'zone',
+/// or have nothing to do with 'qname'
+pub fn scrub(response: &mut DnsPacket, qname: &str, zone: &str, server: SocketAddr) {
+    for record in response.scrub(qname, zone) {
+        log_warn!("Dropping record from {} unrelated to {} or outside of \"{}.\": {}", server, qname, zone, record);
+    }
+}
+
+/// Resolve 'qname' starting from the root servers and following the
+/// referrals down to an authoritative server
+pub fn recursive_lookup(qname: &str, qtype: QueryType, hints: &RootHints, policy: &RetryPolicy)
+    -> Result<DnsPacket> {
+    recursive_lookup_depth(qname, qtype, hints, policy, 0)
+}
+
+fn recursive_lookup_depth(qname: &str, qtype: QueryType, hints: &RootHints, policy: &RetryPolicy,
+                          depth: usize) -> Result<DnsPacket> {
+    if depth > MAX_NS_DEPTH {
+        return Err(Error::Upstream(format!("Too many nested lookups resolving {}", qname)));
+    }
+
+    log_info!("Attempting lookup of {:?} {} from the root servers", qtype, qname);
+    let roots = hints.servers.iter().map(|(_, addr)| NameServer::Address(*addr)).collect();
+    follow_referrals(qname, qtype, roots, hints, policy, depth)
+}
+
+/// Name server of a zone, known by address when the referral came with glue
+enum NameServer {
+    Address(Ipv4Addr),
+    Name(String)
+}
+
+/// Ask the name servers of a zone in turn until one of them gives a usable
+/// response: anything but a timeout, an error or a lame SERVFAIL or REFUSED
+fn query_zone(qname: &str, qtype: QueryType, servers: &[NameServer], zone: &str, hints: &RootHints,
+              policy: &RetryPolicy, depth: usize) -> Result<DnsPacket> {
+    let mut last_err = Error::Upstream(format!("No name servers for \"{}.\"", zone));
+
+    for ns in servers {
+        let addr = match ns {
+            NameServer::Address(addr) => *addr,
+            // Referral without glue: resolve the address of the name server first
+            NameServer::Name(name) => {
+                match recursive_lookup_depth(name, QueryType::A, hints, policy, depth + 1) {
+                    Ok(response) => match response.get_first_a() {
+                        Some(addr) => addr,
+                        None => {
+                            last_err = Error::Upstream(format!("No address for name server {}", name));
+                            continue;
+                        }
+                    },
+                    Err(e) => {
+                        last_err = e;
+                        continue;
+                    }
+                }
+            }
+        };
+
+        let server = SocketAddr::from((addr, 53));
+        let mut response = match lookup(qname, qtype, server, false, policy) {
+            Ok(x) => x,
+            Err(e) => {
+                log_warn!("Name server {} of \"{}.\" failed: {}", server, zone, e);
+                last_err = e;
+                continue;
+            }
+        };
+        scrub(&mut response, qname, zone, server);
+
+        // The server doesn't serve the zone after all, or is broken
+        let lame = matches!(response.header.rescode, ResultCode::SERVFAIL | ResultCode::REFUSED)
+            && !response.header.authoritative_answer
+            && response.get_referral_zone(qname).is_none();
+        if lame {
+            log_warn!("Name server {} of \"{}.\" answered {:?}", server, zone, response.header.rescode);
+            last_err = Error::Upstream(format!("{:?} from {}", response.header.rescode, server));
+            continue;
+        }
+
+        return Ok(response);
+    }
+
+    Err(last_err)
+}
+
+/// Ask the name servers of the zone 'servers' belong to, starting from the
+/// root, and keep following the referrals until an answer is found
+fn follow_referrals(qname: &str, qtype: QueryType, mut servers: Vec<NameServer>, hints: &RootHints,
+                    policy: &RetryPolicy, depth: usize) -> Result<DnsPacket> {
+    // Zone the current servers are responsible for, the root servers first
+    let mut zone = String::new();
+
+    for _ in 0..MAX_REFERRALS {
+        let response = query_zone(qname, qtype, &servers, &zone, hints, policy, depth)?;
+
+        // Answers, authoritative replies and name errors are final
+        if (!response.answers.is_empty() && response.header.rescode == ResultCode::NOERROR)
+            || response.header.authoritative_answer
+            || response.header.rescode == ResultCode::NXDOMAIN {
+            return Ok(response);
+        }
+
+        // A referral must lead further down, not sideways or back up.
+        // If there is no referral at all this is the best we can do.
+        match response.get_referral_zone(qname) {
+            Some(child) if child.len() > zone.len() => zone = child.to_lowercase(),
+            _ => return Ok(response),
+        }
+
+        // The servers with glue first, they can be asked directly
+        servers = response.get_resolved_ns(qname)
+            .into_iter()
+            .map(NameServer::Address)
+            .chain(response.get_unresolved_ns(qname)
+                .into_iter()
+                .map(|name| NameServer::Name(name.to_string())))
+            .collect();
+    }
+
+    Err(Error::Upstream(format!("Too many referrals resolving {}", qname)))
+}
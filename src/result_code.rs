@@ -4,23 +4,40 @@
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ResultCode {
-    NOERROR = 0,
-    FORMERR = 1,
-    SERVFAIL = 2,
-    NXDOMAIN = 3,
-    NOTIMP = 4,
-    REFUSED = 5
+    UNKNOWN(u16), // codes we don't handle, e.g. NOTAUTH, passed on as they are
+    NOERROR,
+    FORMERR,
+    SERVFAIL,
+    NXDOMAIN,
+    NOTIMP,
+    REFUSED,
+    BADVERS // extended code, the upper bits travel in the OPT record
 }
 
 impl ResultCode {
-    pub fn from_num(num: u8) -> ResultCode {
+    pub fn to_num(self) -> u16 {
+        match self {
+            ResultCode::UNKNOWN(x) => x,
+            ResultCode::NOERROR => 0,
+            ResultCode::FORMERR => 1,
+            ResultCode::SERVFAIL => 2,
+            ResultCode::NXDOMAIN => 3,
+            ResultCode::NOTIMP => 4,
+            ResultCode::REFUSED => 5,
+            ResultCode::BADVERS => 16,
+        }
+    }
+
+    pub fn from_num(num: u16) -> ResultCode {
         match num {
+            0 => ResultCode::NOERROR,
             1 => ResultCode::FORMERR,
             2 => ResultCode::SERVFAIL,
             3 => ResultCode::NXDOMAIN,
             4 => ResultCode::NOTIMP,
             5 => ResultCode::REFUSED,
-            0 | _ => ResultCode::NOERROR,
+            16 => ResultCode::BADVERS,
+            _ => ResultCode::UNKNOWN(num),
         }
     }
 }
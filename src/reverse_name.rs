@@ -0,0 +1,70 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/* == Reverse names == */
+// Addresses are looked up in reverse, as names under in-addr.arpa (IPv4)
+// or ip6.arpa (IPv6) with the least significant part first
+
+/// Reverse name of an IPv4 address
+/// Example: 192.0.2.1 -> 1.2.0.192.in-addr.arpa
+pub fn ipv4_to_name(addr: &Ipv4Addr) -> String {
+    let o = addr.octets();
+    format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+}
+
+/// Reverse name of an IPv6 address, one label for every nibble
+/// Example: 2001:db8::1 -> 1.0.0.0. ... .8.b.d.0.1.0.0.2.ip6.arpa
+pub fn ipv6_to_name(addr: &Ipv6Addr) -> String {
+    let mut name = String::with_capacity(72);
+    for b in addr.octets().iter().rev() {
+        name.push_str(&format!("{:x}.{:x}.", b & 0x0F, b >> 4));
+    }
+    name.push_str("ip6.arpa");
+    name
+}
+
+/// Reverse name of an address of either family
+pub fn ip_to_name(addr: &IpAddr) -> String {
+    match addr {
+        IpAddr::V4(x) => ipv4_to_name(x),
+        IpAddr::V6(x) => ipv6_to_name(x),
+    }
+}
+
+/// Address of a complete reverse name, None for any other name
+pub fn name_to_ip(name: &str) -> Option<IpAddr> {
+    let name = name.trim_end_matches('.').to_lowercase();
+
+    if let Some(rest) = name.strip_suffix(".in-addr.arpa") {
+        let mut octets = [0u8; 4];
+        let labels: Vec<&str> = rest.split('.').collect();
+        if labels.len() != 4 {
+            return None;
+        }
+        for (i, label) in labels.iter().rev().enumerate() {
+            // Only digits and no leading zeros, like the canonical name
+            if !label.bytes().all(|b| b.is_ascii_digit()) || (label.len() > 1 && label.starts_with('0')) {
+                return None;
+            }
+            octets[i] = label.parse().ok()?;
+        }
+        return Some(IpAddr::V4(Ipv4Addr::from(octets)));
+    }
+
+    if let Some(rest) = name.strip_suffix(".ip6.arpa") {
+        let mut octets = [0u8; 16];
+        let labels: Vec<&str> = rest.split('.').collect();
+        if labels.len() != 32 {
+            return None;
+        }
+        for (i, label) in labels.iter().rev().enumerate() {
+            if label.len() != 1 {
+                return None;
+            }
+            let nibble = u8::from_str_radix(label, 16).ok()?;
+            octets[i / 2] |= if i % 2 == 0 { nibble << 4 } else { nibble };
+        }
+        return Some(IpAddr::V6(Ipv6Addr::from(octets)));
+    }
+
+    None
+}
@@ -0,0 +1,95 @@
+use std::fs;
+use std::net::Ipv4Addr;
+use crate::error::{Error, Result};
+
+/* == RootHints == */
+/// Addresses of the root servers, the starting point of every recursive lookup
+
+#[derive(Clone, Debug)]
+pub struct RootHints {
+    pub servers: Vec<(String, Ipv4Addr)>
+}
+
+/// Built-in table of the IANA root servers
+const DEFAULT_ROOT_SERVERS: [(&str, [u8; 4]); 13] = [
+    ("a.root-servers.net", [198, 41, 0, 4]),
+    ("b.root-servers.net", [170, 247, 170, 2]),
+    ("c.root-servers.net", [192, 33, 4, 12]),
+    ("d.root-servers.net", [199, 7, 91, 13]),
+    ("e.root-servers.net", [192, 203, 230, 10]),
+    ("f.root-servers.net", [192, 5, 5, 241]),
+    ("g.root-servers.net", [192, 112, 36, 4]),
+    ("h.root-servers.net", [198, 97, 190, 53]),
+    ("i.root-servers.net", [192, 36, 148, 17]),
+    ("j.root-servers.net", [192, 58, 128, 30]),
+    ("k.root-servers.net", [193, 0, 14, 129]),
+    ("l.root-servers.net", [199, 7, 83, 42]),
+    ("m.root-servers.net", [202, 12, 27, 33]),
+];
+
+impl Default for RootHints {
+    fn default() -> Self {
+        RootHints::new()
+    }
+}
+
+impl RootHints {
+    /// Root hints compiled into the server
+    pub fn new() -> RootHints {
+        RootHints {
+            servers: DEFAULT_ROOT_SERVERS
+                .iter()
+                .map(|(name, ip)| (name.to_string(), Ipv4Addr::from(*ip)))
+                .collect()
+        }
+    }
+
+    /// Load root hints from a file in the format of 'named.root'
+    ///
+    /// Example line: A.ROOT-SERVERS.NET.  3600000  A  198.41.0.4
+    /// Only the A records are used, everything after a ';' is a comment
+    pub fn from_file(path: &str) -> Result<RootHints> {
+        let content = fs::read_to_string(path)?;
+        RootHints::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<RootHints> {
+        let mut servers = Vec::new();
+
+        for (n, line) in content.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            // name [ttl] [class] type data
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let type_pos = match fields.iter().skip(1).position(|f| {
+                f.eq_ignore_ascii_case("A") || f.eq_ignore_ascii_case("AAAA")
+                    || f.eq_ignore_ascii_case("NS")
+            }) {
+                Some(x) => x + 1,
+                None => return Err(Error::Invalid(format!("Invalid root hints line {}: {}", n + 1, line))),
+            };
+
+            if !fields[type_pos].eq_ignore_ascii_case("A") {
+                continue;
+            }
+
+            let data = match fields.get(type_pos + 1) {
+                Some(x) => x,
+                None => return Err(Error::Invalid(format!("Missing address on root hints line {}", n + 1))),
+            };
+            let addr = data.parse::<Ipv4Addr>()?;
+            let name = fields[0].trim_end_matches('.').to_lowercase();
+
+            servers.push((name, addr));
+        }
+
+        if servers.is_empty() {
+            return Err(Error::Invalid("Root hints contain no A records".to_string()));
+        }
+
+        Ok(RootHints { servers })
+    }
+}
@@ -0,0 +1,717 @@
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::mpsc::{self, TrySendError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use crate::byte_packet_buffer::{BytePacketBuffer, MAX_PACKET_SIZE, UDP_PACKET_SIZE};
+use crate::context::ServerContext;
+use crate::dns_packet::DnsPacket;
+use crate::dns_question::DnsQuestion;
+use crate::dns_record::DnsRecord;
+use crate::query_class::QueryClass;
+use crate::query_type::QueryType;
+use crate::resolver::recursive_lookup;
+use crate::result_code::ResultCode;
+use crate::tcp;
+use crate::log::{log_info, log_warn};
+use crate::error::{Error, Result};
+
+/* == Server == */
+
+/// UDP payload size we advertise to EDNS clients, and the most we send them
+const EDNS_PAYLOAD_SIZE: u16 = 4096;
+
+/// Longest CNAME chain followed for a single query
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// Resolve 'qname', following the CNAME records of the answer until the
+/// records of type 'qtype' are found. The answer holds the whole chain,
+/// the rest of the response comes from the last name of the chain.
+fn resolve(qname: &str, qtype: QueryType, context: &ServerContext) -> Result<DnsPacket> {
+    let mut packet = resolve_name(qname, qtype, context)?;
+
+    // The CNAME itself is the answer to these
+    if matches!(qtype, QueryType::CNAME | QueryType::UNKNOWN(255)) {
+        return Ok(packet);
+    }
+
+    let mut chain = vec![qname.to_lowercase()];
+    let mut asked = qname.to_lowercase();
+    while packet.header.rescode == ResultCode::NOERROR {
+        // Follow the chain as far as the answer goes
+        let mut name = chain.last().unwrap().clone();
+        while let Some(host) = packet.answers.iter().find_map(|record| match record {
+            DnsRecord::CNAME { domain, host, .. } if domain.eq_ignore_ascii_case(&name) => Some(host.to_lowercase()),
+            _ => None,
+        }) {
+            if chain.contains(&host) {
+                return Err(Error::Upstream(format!("CNAME loop resolving {} at {}", qname, host)));
+            }
+            if chain.len() > MAX_CNAME_CHAIN {
+                return Err(Error::Upstream(format!("CNAME chain of {} too long", qname)));
+            }
+            chain.push(host.clone());
+            name = host;
+        }
+
+        // Done when the records were found, or the last name has none
+        let complete = packet.answers
+            .iter()
+            .any(|r| r.get_querytype() == qtype && r.get_domain().eq_ignore_ascii_case(&name));
+        if complete || name == asked {
+            break;
+        }
+
+        // The answer ends with a CNAME, ask for its target
+        log_info!("Following CNAME of {} to {}", qname, name);
+        let next = resolve_name(&name, qtype, context)?;
+        asked = name;
+        packet.header.rescode = next.header.rescode;
+        for record in next.answers {
+            if !packet.answers.contains(&record) {
+                packet.answers.push(record);
+            }
+        }
+        packet.authorities = next.authorities;
+        packet.resources = next.resources;
+    }
+
+    Ok(packet)
+}
+
+/// Answer from the local data or the cache when possible, otherwise
+/// resolve recursively, or forward to the upstream servers when there are
+/// some, and remember the answer
+fn resolve_name(qname: &str, qtype: QueryType, context: &ServerContext) -> Result<DnsPacket> {
+    if let Some(packet) = context.hosts.answer(qname, qtype) {
+        log_info!("Local data: {:?} {}", qtype, qname);
+        return Ok(packet);
+    }
+
+    if let Some(packet) = context.cache.lock().unwrap().lookup(qname, qtype, QueryClass::IN) {
+        log_info!("Cache hit: {:?} {}", qtype, qname);
+        return Ok(packet);
+    }
+
+    let policy = context.config.retry_policy();
+    let result = if context.upstreams.is_empty() {
+        recursive_lookup(qname, qtype, &context.hints, &policy)?
+    } else {
+        context.upstreams.forward(qname, qtype, &policy)?
+    };
+
+    let mut cache = context.cache.lock().unwrap();
+    match result.header.rescode {
+        ResultCode::NOERROR if !result.answers.is_empty() => {
+            cache.store(qname, qtype, QueryClass::IN, result.answers.clone());
+        }
+        ResultCode::NOERROR | ResultCode::NXDOMAIN => {
+            cache.store_negative(qname, qtype, QueryClass::IN, result.header.rescode, &result.authorities);
+        }
+        _ => {}
+    }
+
+    Ok(result)
+}
+
+/// Answer the CHAOS class queries about the server itself: its version
+/// and its identity, when they are configured. Anything else is refused.
+fn answer_chaos(question: &DnsQuestion, context: &ServerContext) -> DnsPacket {
+    let mut packet = DnsPacket::new();
+    packet.header.authoritative_answer = true;
+
+    let name = question.name.trim_end_matches('.').to_lowercase();
+    let value = match name.as_str() {
+        "version.bind" | "version.server" => context.config.version.as_ref(),
+        "hostname.bind" | "id.server" => context.config.identity.as_ref(),
+        _ => None,
+    };
+
+    match value {
+        Some(value) => {
+            if matches!(question.qtype, QueryType::TXT | QueryType::UNKNOWN(255)) {
+                packet.answers.push(DnsRecord::TXT {
+                    domain: name,
+                    strings: vec![value.as_bytes().to_vec()],
+                    class: QueryClass::CH,
+                    ttl: 0
+                });
+            }
+        }
+        None => packet.header.rescode = ResultCode::REFUSED,
+    }
+
+    packet
+}
+
+/// Additional section processing: attach the addresses of the SRV targets
+/// we have in the cache, sparing the client a lookup for each of them
+fn add_target_addresses(response: &mut DnsPacket, context: &ServerContext) {
+    let targets: Vec<String> = response.answers
+        .iter()
+        .filter_map(|record| match record {
+            DnsRecord::SRV { target, .. } if !target.is_empty() => Some(target.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let mut cache = context.cache.lock().unwrap();
+    for target in targets {
+        for qtype in [QueryType::A, QueryType::AAAA] {
+            let present = response.resources
+                .iter()
+                .any(|r| r.get_querytype() == qtype && r.get_domain().eq_ignore_ascii_case(&target));
+            if present {
+                continue;
+            }
+
+            if let Some(packet) = cache.lookup(&target, qtype, QueryClass::IN) {
+                response.resources.extend(packet.answers
+                    .into_iter()
+                    .filter(|r| r.get_querytype() == qtype));
+            }
+        }
+    }
+}
+
+/// Whether 'record' only matters to clients validating DNSSEC
+fn is_dnssec_record(record: &DnsRecord, qtype: QueryType) -> bool {
+    let rtype = record.get_querytype();
+    rtype != qtype && matches!(rtype, QueryType::RRSIG | QueryType::NSEC | QueryType::NSEC3)
+}
+
+/// Build the response to a request, the same for UDP and TCP,
+/// or any other transport the request came from
+pub fn build_response(mut request: DnsPacket, context: &ServerContext) -> DnsPacket {
+    // Create and initialzie response packet
+    let mut response = DnsPacket::new();
+    response.header.id = request.header.id;
+    response.header.recursion_desired = true;
+    response.header.recursion_available = true;
+    response.header.response = true;
+    response.header.opcode = request.header.opcode;
+
+    // Answer EDNS with EDNS, only version 0 is supported (RFC 6891)
+    if let Some(version) = request.edns_version() {
+        response.set_edns(EDNS_PAYLOAD_SIZE, request.dnssec_ok());
+
+        if version > 0 {
+            response.questions = request.questions;
+            response.header.rescode = ResultCode::BADVERS;
+            return response;
+        }
+    }
+
+    // Only standard queries are supported, not e.g. NOTIFY or UPDATE
+    if request.header.opcode != 0 {
+        response.questions = request.questions;
+        response.header.rescode = ResultCode::NOTIMP;
+        return response;
+    }
+
+    // A query holds a single question, what to do with several is undefined
+    if request.questions.len() > 1 {
+        response.header.rescode = ResultCode::FORMERR;
+        return response;
+    }
+
+    if let Some(question) = request.questions.pop() {
+        log_info!("Received query: {:?}", question);
+
+        // Only the Internet class is resolved, CHAOS is answered locally
+        let result = match question.qclass {
+            QueryClass::IN => resolve(&question.name, question.qtype, context),
+            QueryClass::CH => Ok(answer_chaos(&question, context)),
+            _ => {
+                let mut packet = DnsPacket::new();
+                packet.header.rescode = ResultCode::REFUSED;
+                Ok(packet)
+            }
+        };
+
+        match result {
+            Ok(mut result) => {
+                let qtype = question.qtype;
+                response.questions.push(question);
+                response.header.rescode = result.header.rescode;
+
+                // We always ask with the DO flag, signatures and denial of
+                // existence records are left out for clients without it
+                if !request.dnssec_ok() {
+                    result.answers.retain(|r| !is_dnssec_record(r, qtype));
+                    result.authorities.retain(|r| !is_dnssec_record(r, qtype));
+                    result.resources.retain(|r| !is_dnssec_record(r, qtype));
+                }
+
+                for res in result.answers {
+                    log_info!("Answer: {}", res);
+                    response.answers.push(res);
+                }
+                for res in result.authorities {
+                    log_info!("Authorities: {}", res);
+                    response.authorities.push(res);
+                }
+                for res in result.resources {
+                    log_info!("Resources: {}", res);
+                    response.resources.push(res);
+                }
+
+                add_target_addresses(&mut response, context);
+            }
+            Err(e) => {
+                log_warn!("Lookup failed: {}", e);
+                response.questions.push(question);
+                response.header.rescode = ResultCode::SERVFAIL;
+            }
+        }
+    }
+    else {
+        response.header.rescode = ResultCode::FORMERR;
+    }
+
+    response
+}
+
+/// Response to a request that isn't answered the usual way, e.g. FORMERR when
+/// it can't be parsed, with the id, opcode and RD flag salvaged from what is
+/// left of the header. None when there isn't even an id to answer to, or
+/// when the message is a response itself, which is never answered.
+fn error_response(buffer: &BytePacketBuffer, rescode: ResultCode) -> Option<DnsPacket> {
+    let id = ((buffer.get(0).ok()? as u16) << 8) | buffer.get(1).ok()? as u16;
+    let flags = buffer.get(2).unwrap_or(0);
+    if flags & 0x80 != 0 {
+        return None;
+    }
+
+    let mut response = DnsPacket::new();
+    response.header.id = id;
+    response.header.opcode = (flags >> 3) & 0x0F;
+    response.header.recursion_desired = (flags & 1) > 0;
+    response.header.recursion_available = true;
+    response.header.response = true;
+    response.header.rescode = rescode;
+
+    Some(response)
+}
+
+/// Answer a UDP request already received from 'src'
+pub fn handle_datagram(socket: &UdpSocket, mut req_buffer: BytePacketBuffer, src: SocketAddr,
+                       context: &ServerContext) -> Result<()> {
+    // Parse the request, malformed ones are answered with FORMERR
+    // and responses are ignored
+    let (mut response, max_size) = match DnsPacket::from_buffer(&mut req_buffer) {
+        Ok(request) if request.header.response => return Ok(()),
+        Ok(request) => {
+            // Size the response to what the client accepts
+            let max_size = request.max_udp_size().min(EDNS_PAYLOAD_SIZE as usize);
+            (build_response(request, context), max_size)
+        }
+        Err(e) if e.is_format_error() => {
+            log_warn!("Malformed query from {}: {}", src, e);
+            match error_response(&req_buffer, ResultCode::FORMERR) {
+                Some(x) => (x, UDP_PACKET_SIZE),
+                None => return Ok(()),
+            }
+        }
+        Err(e) => return Err(e),
+    };
+
+    send_response(socket, &mut response, max_size, src)
+}
+
+/// Write a response of at most 'max_size' bytes, dropping what doesn't fit
+/// and setting the TC flag. A response holding a record that can't be
+/// written is replaced with SERVFAIL, the client still gets an answer.
+fn write_response(response: &mut DnsPacket, max_size: usize) -> Result<BytePacketBuffer> {
+    let mut res_buffer = BytePacketBuffer::with_size(max_size);
+
+    if let Err(e) = response.write_truncated(&mut res_buffer) {
+        log_warn!("Failed to write response: {}", e);
+        response.answers.clear();
+        response.authorities.clear();
+        response.resources.clear();
+        response.header.truncate_message = false;
+        response.header.rescode = ResultCode::SERVFAIL;
+
+        // The question itself may be what can't be written, e.g. a name
+        // whose invalid UTF-8 grew past the length of a label when decoded
+        if response.write_truncated(&mut res_buffer).is_err() {
+            response.questions.clear();
+            response.write_truncated(&mut res_buffer)?;
+        }
+    }
+
+    Ok(res_buffer)
+}
+
+/// Send a response of at most 'max_size' bytes over UDP, when it doesn't
+/// fit the client will retry over TCP
+fn send_response(socket: &UdpSocket, response: &mut DnsPacket, max_size: usize, src: SocketAddr)
+    -> Result<()> {
+    let res_buffer = write_response(response, max_size)?;
+
+    let len = res_buffer.pos();
+    let data = res_buffer.get_range(0, len)?;
+
+    socket.send_to(data, src)?;
+
+    Ok(())
+}
+
+/// Serve the UDP queries. This thread receives the datagrams and queues them
+/// for the worker threads, which do the lookups. When the queue is full the
+/// query is answered with SERVFAIL at once, so that a few slow lookups can't
+/// hold up every client.
+pub fn run_udp_server(context: Arc<ServerContext>) -> Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", context.config.port))?;
+
+    let (sender, receiver) = mpsc::sync_channel::<(BytePacketBuffer, SocketAddr)>(context.config.queue_size);
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    for _ in 0..context.config.workers.max(1) {
+        let socket = socket.try_clone()?;
+        let receiver = Arc::clone(&receiver);
+        let context = Arc::clone(&context);
+
+        thread::spawn(move || loop {
+            // Idle workers take turns waiting for the next datagram
+            let (req_buffer, src) = match receiver.lock().unwrap().recv() {
+                Ok(x) => x,
+                Err(_) => return,
+            };
+
+            if let Err(e) = handle_datagram(&socket, req_buffer, src, &context) {
+                log_warn!("Error: {}", e);
+            }
+        });
+    }
+
+    loop {
+        let mut req_buffer = BytePacketBuffer::with_size(MAX_PACKET_SIZE);
+        let (len, src) = match socket.recv_from(&mut req_buffer.buf) {
+            Ok(x) => x,
+            Err(e) => {
+                log_warn!("Failed to receive UDP query: {}", e);
+                continue;
+            }
+        };
+        req_buffer.resize(len);
+
+        let (mut req_buffer, src) = match sender.try_send((req_buffer, src)) {
+            Ok(()) => continue,
+            Err(TrySendError::Full(x)) => x,
+            Err(TrySendError::Disconnected(_)) => return Err(Error::Invalid("No workers left".to_string())),
+        };
+
+        log_warn!("Too many queries in flight, failing query from {}", src);
+        let mut response = match error_response(&req_buffer, ResultCode::SERVFAIL) {
+            Some(x) => x,
+            None => continue,
+        };
+        // Echo the question, clients check that it matches theirs
+        if let Ok(request) = DnsPacket::from_buffer(&mut req_buffer) {
+            response.questions = request.questions;
+        }
+
+        if let Err(e) = send_response(&socket, &mut response, UDP_PACKET_SIZE, src) {
+            log_warn!("Error: {}", e);
+        }
+    }
+}
+
+/// Answer the queries sent on a TCP connection until the client closes it
+/// or stays idle for longer than the configured timeout.
+/// Pipelined queries are answered one after the other.
+pub fn handle_tcp_connection(mut stream: TcpStream, context: &ServerContext) -> Result<()> {
+    // Sockets refuse a zero timeout
+    let idle_timeout = Duration::from_secs(context.config.tcp_idle_timeout.max(1));
+    stream.set_read_timeout(Some(idle_timeout))?;
+
+    loop {
+        let mut req_buffer = match tcp::read_message(&mut stream) {
+            Ok(x) => x,
+            // Connection closed by the client or idle for too long
+            Err(Error::Io(ref e)) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(Error::Timeout) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let mut response = match DnsPacket::from_buffer(&mut req_buffer) {
+            Ok(request) if request.header.response => continue,
+            Ok(request) => build_response(request, context),
+            Err(e) if e.is_format_error() => {
+                log_warn!("Malformed query over TCP: {}", e);
+                match error_response(&req_buffer, ResultCode::FORMERR) {
+                    Some(x) => x,
+                    None => continue,
+                }
+            }
+            Err(e) => return Err(e),
+        };
+
+        let res_buffer = write_response(&mut response, MAX_PACKET_SIZE)?;
+
+        let len = res_buffer.pos();
+        tcp::write_message(&mut stream, &res_buffer, len)?;
+    }
+}
+
+/// Slot of a TCP connection in the count of open ones, given back when
+/// the connection is done with
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Accept TCP connections in the background, each one served by its own
+/// thread. Connections beyond the configured maximum are closed at once
+/// (RFC 7766 6.2.2), so that idle clients can't take every thread.
+pub fn start_tcp_server(context: Arc<ServerContext>) -> Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", context.config.port))?;
+    let open = Arc::new(AtomicUsize::new(0));
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(x) => x,
+                Err(e) => {
+                    log_warn!("Failed to accept TCP connection: {}", e);
+                    continue;
+                }
+            };
+
+            if open.fetch_add(1, Ordering::SeqCst) >= context.config.tcp_connections.max(1) {
+                open.fetch_sub(1, Ordering::SeqCst);
+                let peer = stream.peer_addr().map(|x| x.to_string()).unwrap_or_default();
+                log_warn!("Too many TCP connections, closing the one from {}", peer);
+                continue;
+            }
+            let slot = ConnectionSlot(Arc::clone(&open));
+
+            let context = Arc::clone(&context);
+            thread::spawn(move || {
+                let _slot = slot;
+                if let Err(e) = handle_tcp_connection(stream, &context) {
+                    log_warn!("Error: {}", e);
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+
+    const ID: u16 = 0xbeef;
+
+    /// Header with the given flags and question count, the other counts zero
+    fn header(flags: u16, qdcount: u16) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&ID.to_be_bytes());
+        data.extend_from_slice(&flags.to_be_bytes());
+        data.extend_from_slice(&qdcount.to_be_bytes());
+        data.extend_from_slice(&[0; 6]);
+        data
+    }
+
+    /// Question for 'name' of type A, class IN
+    fn question(name: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        for label in name.split('.') {
+            data.push(label.len() as u8);
+            data.extend_from_slice(label.as_bytes());
+        }
+        data.extend_from_slice(&[0, 0, 1, 0, 1]);
+        data
+    }
+
+    fn buffer(data: &[u8]) -> BytePacketBuffer {
+        let mut buffer = BytePacketBuffer::with_size(data.len());
+        buffer.buf.copy_from_slice(data);
+        buffer
+    }
+
+    /// Hand 'data' to the server as a datagram and return the reply, if any
+    fn exchange(data: &[u8]) -> Option<DnsPacket> {
+        let mut context = ServerContext::new(ServerConfig::new()).unwrap();
+        context.hosts.add("host.example", "192.0.2.1".parse().unwrap());
+
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+
+        handle_datagram(&server, buffer(data), client.local_addr().unwrap(), &context).unwrap();
+
+        let mut reply = BytePacketBuffer::with_size(MAX_PACKET_SIZE);
+        let len = match client.recv(&mut reply.buf) {
+            Ok(len) => len,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => return None,
+            Err(e) => panic!("{}", e),
+        };
+        reply.resize(len);
+        Some(DnsPacket::from_buffer(&mut reply).unwrap())
+    }
+
+    fn assert_reply(data: &[u8], rescode: ResultCode) -> DnsPacket {
+        let reply = exchange(data).expect("no reply");
+        assert_eq!(reply.header.id, ID);
+        assert!(reply.header.response);
+        assert_eq!(reply.header.rescode, rescode);
+        reply
+    }
+
+    #[test]
+    fn well_formed_query_is_answered() {
+        let mut data = header(0x0100, 1);
+        data.extend(question("host.example"));
+        let reply = assert_reply(&data, ResultCode::NOERROR);
+        assert_eq!(reply.answers.len(), 1);
+    }
+
+    #[test]
+    fn short_header_gets_formerr() {
+        // RD is salvaged along with the id
+        let reply = assert_reply(&[0xbe, 0xef, 0x01, 0x00, 0x00], ResultCode::FORMERR);
+        assert!(reply.header.recursion_desired);
+
+        assert!(matches!(DnsPacket::from_buffer(&mut buffer(&header(0, 1)[..11])),
+                         Err(Error::Truncated { .. })));
+    }
+
+    #[test]
+    fn no_id_gets_no_reply() {
+        assert!(exchange(&[0xbe]).is_none());
+    }
+
+    #[test]
+    fn no_question_gets_formerr() {
+        assert_reply(&header(0x0100, 0), ResultCode::FORMERR);
+    }
+
+    #[test]
+    fn two_questions_get_formerr() {
+        let mut data = header(0x0100, 2);
+        data.extend(question("host.example"));
+        data.extend(question("host.example"));
+        assert_reply(&data, ResultCode::FORMERR);
+    }
+
+    #[test]
+    fn missing_question_gets_formerr() {
+        let mut data = header(0x0100, 3);
+        data.extend(question("host.example"));
+        assert_reply(&data, ResultCode::FORMERR);
+
+        assert!(matches!(DnsPacket::from_buffer(&mut buffer(&data)), Err(Error::Truncated { .. })));
+    }
+
+    #[test]
+    fn bad_label_gets_formerr() {
+        // 0x40 and 0x80 are neither a label length nor a pointer, which
+        // also rejects labels longer than 63 bytes
+        for label in [0x40u8, 0x7f, 0x80, 0xbf] {
+            let mut data = header(0x0100, 1);
+            data.push(label);
+            data.extend_from_slice(&[b'a'; 0x40]);
+            data.extend_from_slice(&[0, 0, 1, 0, 1]);
+            assert_reply(&data, ResultCode::FORMERR);
+
+            assert!(matches!(DnsPacket::from_buffer(&mut buffer(&data)), Err(Error::BadLabel { offset: 12 })));
+        }
+    }
+
+    #[test]
+    fn response_gets_no_reply() {
+        let mut data = header(0x8100, 1);
+        data.extend(question("host.example"));
+        assert!(exchange(&data).is_none());
+
+        // Not even when it is malformed
+        assert!(exchange(&header(0x8100, 1)).is_none());
+    }
+
+    #[test]
+    fn other_opcode_gets_notimp() {
+        // NOTIFY
+        let mut data = header(4 << 11, 1);
+        data.extend(question("host.example"));
+        let reply = assert_reply(&data, ResultCode::NOTIMP);
+        assert_eq!(reply.header.opcode, 4);
+        assert!(reply.answers.is_empty());
+    }
+
+    #[test]
+    fn unwritable_response_becomes_servfail() {
+        let mut response = DnsPacket::new();
+        response.header.id = ID;
+        response.header.response = true;
+        response.answers.push(DnsRecord::CAA {
+            domain: "example.com".to_string(),
+            flags: 0,
+            tag: "issue-x".to_string(),
+            value: Vec::new(),
+            class: QueryClass::IN,
+            ttl: 300
+        });
+
+        let mut buffer = write_response(&mut response, MAX_PACKET_SIZE).unwrap();
+        buffer.seek(0).unwrap();
+        let reply = DnsPacket::from_buffer(&mut buffer).unwrap();
+        assert_eq!(reply.header.id, ID);
+        assert_eq!(reply.header.rescode, ResultCode::SERVFAIL);
+        assert!(reply.answers.is_empty());
+    }
+
+    #[test]
+    fn unwritable_question_still_gets_servfail() {
+        let mut response = DnsPacket::new();
+        response.header.id = ID;
+        response.header.response = true;
+        response.questions.push(DnsQuestion::new("\u{fffd}".repeat(30), QueryType::TXT, QueryClass::CH));
+
+        let mut buffer = write_response(&mut response, MAX_PACKET_SIZE).unwrap();
+        buffer.seek(0).unwrap();
+        let reply = DnsPacket::from_buffer(&mut buffer).unwrap();
+        assert_eq!(reply.header.id, ID);
+        assert_eq!(reply.header.rescode, ResultCode::SERVFAIL);
+        assert!(reply.questions.is_empty());
+
+        // The same name sent as invalid UTF-8 parses, and is answered
+        let mut data = header(0x0100, 1);
+        data.push(30);
+        data.extend_from_slice(&[0xff; 30]);
+        data.extend_from_slice(&[0, 0, 16, 0, 3]);
+        assert_reply(&data, ResultCode::SERVFAIL);
+    }
+
+    #[test]
+    fn oversized_tcp_response_is_truncated() {
+        let mut response = DnsPacket::new();
+        response.header.id = ID;
+        response.header.response = true;
+        for i in 0..300 {
+            response.answers.push(DnsRecord::TXT {
+                domain: format!("txt{}.example.com", i),
+                strings: vec![vec![b'x'; 255]],
+                class: QueryClass::IN,
+                ttl: 300
+            });
+        }
+
+        let mut buffer = write_response(&mut response, MAX_PACKET_SIZE).unwrap();
+        assert!(buffer.pos() <= MAX_PACKET_SIZE);
+        buffer.seek(0).unwrap();
+        let reply = DnsPacket::from_buffer(&mut buffer).unwrap();
+        assert!(reply.header.truncate_message);
+        assert!(!reply.answers.is_empty() && reply.answers.len() < 300);
+    }
+}
@@ -0,0 +1,199 @@
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use crate::byte_packet_buffer::BytePacketBuffer;
+use crate::error::{Error, Result};
+
+/* == SvcParam == */
+
+/// Service parameter of SVCB and HTTPS records (RFC 9460)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SvcParam {
+    Mandatory(Vec<u16>), // keys the client must understand
+    Alpn(Vec<Vec<u8>>), // protocol ids, e.g. h2 and h3
+    NoDefaultAlpn,
+    Port(u16),
+    Ipv4Hint(Vec<Ipv4Addr>),
+    Ech(Vec<u8>), // encrypted client hello configuration
+    Ipv6Hint(Vec<Ipv6Addr>),
+    Unknown {
+        key: u16,
+        value: Vec<u8> // raw value, passed on untouched
+    },
+}
+
+impl SvcParam {
+    pub fn key(&self) -> u16 {
+        match *self {
+            SvcParam::Mandatory(_) => 0,
+            SvcParam::Alpn(_) => 1,
+            SvcParam::NoDefaultAlpn => 2,
+            SvcParam::Port(_) => 3,
+            SvcParam::Ipv4Hint(_) => 4,
+            SvcParam::Ech(_) => 5,
+            SvcParam::Ipv6Hint(_) => 6,
+            SvcParam::Unknown { key, .. } => key,
+        }
+    }
+
+    /// Read the value of the parameter 'key', 'len' bytes long
+    pub fn read(buffer: &mut BytePacketBuffer, key: u16, len: usize) -> Result<SvcParam> {
+        let end = buffer.pos() + len;
+
+        let param = match key {
+            0 => {
+                let mut keys = Vec::new();
+                while buffer.pos() < end {
+                    keys.push(buffer.read_u16()?);
+                }
+                SvcParam::Mandatory(keys)
+            }
+            1 => {
+                let mut ids = Vec::new();
+                while buffer.pos() < end {
+                    ids.push(buffer.read_character_string()?);
+                }
+                SvcParam::Alpn(ids)
+            }
+            2 => SvcParam::NoDefaultAlpn,
+            3 => SvcParam::Port(buffer.read_u16()?),
+            4 => {
+                let mut addrs = Vec::new();
+                while buffer.pos() < end {
+                    addrs.push(Ipv4Addr::from(buffer.read_u32()?));
+                }
+                SvcParam::Ipv4Hint(addrs)
+            }
+            5 => SvcParam::Ech(buffer.read_bytes(len)?),
+            6 => {
+                let mut addrs = Vec::new();
+                while buffer.pos() < end {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(&buffer.read_bytes(16)?);
+                    addrs.push(Ipv6Addr::from(octets));
+                }
+                SvcParam::Ipv6Hint(addrs)
+            }
+            _ => SvcParam::Unknown {
+                key,
+                value: buffer.read_bytes(len)?
+            },
+        };
+
+        if buffer.pos() != end {
+            return Err(Error::Malformed {
+                offset: end - len,
+                reason: format!("Invalid length {} of SvcParam key{}", len, key)
+            });
+        }
+
+        Ok(param)
+    }
+
+    /// Write key, length and value of the parameter
+    pub fn write(&self, buffer: &mut BytePacketBuffer) -> Result<()> {
+        buffer.write_u16(self.key())?;
+
+        let pos = buffer.pos();
+        buffer.write_u16(0)?;
+
+        match *self {
+            SvcParam::Mandatory(ref keys) => {
+                for key in keys {
+                    buffer.write_u16(*key)?;
+                }
+            }
+            SvcParam::Alpn(ref ids) => {
+                for id in ids {
+                    buffer.write_character_string(id)?;
+                }
+            }
+            SvcParam::NoDefaultAlpn => {},
+            SvcParam::Port(port) => buffer.write_u16(port)?,
+            SvcParam::Ipv4Hint(ref addrs) => {
+                for addr in addrs {
+                    buffer.write_bytes(&addr.octets())?;
+                }
+            }
+            SvcParam::Ech(ref config) => buffer.write_bytes(config)?,
+            SvcParam::Ipv6Hint(ref addrs) => {
+                for addr in addrs {
+                    buffer.write_bytes(&addr.octets())?;
+                }
+            }
+            SvcParam::Unknown { ref value, .. } => buffer.write_bytes(value)?,
+        }
+
+        let size = buffer.pos() - (pos + 2);
+        buffer.set_u16(pos, size as u16)?;
+
+        Ok(())
+    }
+}
+
+/// Name of a key in the presentation format
+fn key_name(key: u16) -> String {
+    match key {
+        0 => "mandatory".to_string(),
+        1 => "alpn".to_string(),
+        2 => "no-default-alpn".to_string(),
+        3 => "port".to_string(),
+        4 => "ipv4hint".to_string(),
+        5 => "ech".to_string(),
+        6 => "ipv6hint".to_string(),
+        _ => format!("key{}", key),
+    }
+}
+
+/// Bytes of a value with anything but printable ASCII as \DDD
+fn escaped(bytes: &[u8]) -> String {
+    let mut res = String::new();
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' | b',' => res.push_str(&format!("\\{}", b as char)),
+            0x21..=0x7E => res.push(b as char),
+            _ => res.push_str(&format!("\\{:03}", b)),
+        }
+    }
+    res
+}
+
+/// Base64 encoding, used for ECH configurations and DNSSEC keys
+pub fn base64(bytes: &[u8]) -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut res = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(CHARS[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+    res
+}
+
+/// Parameter in the presentation format, e.g. alpn="h2,h3"
+impl fmt::Display for SvcParam {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", key_name(self.key()))?;
+
+        let values: Vec<String> = match *self {
+            SvcParam::Mandatory(ref keys) => keys.iter().map(|k| key_name(*k)).collect(),
+            SvcParam::Alpn(ref ids) => ids.iter().map(|id| escaped(id)).collect(),
+            SvcParam::NoDefaultAlpn => return Ok(()),
+            SvcParam::Port(port) => vec![port.to_string()],
+            SvcParam::Ipv4Hint(ref addrs) => addrs.iter().map(|a| a.to_string()).collect(),
+            SvcParam::Ech(ref config) => vec![base64(config)],
+            SvcParam::Ipv6Hint(ref addrs) => addrs.iter().map(|a| a.to_string()).collect(),
+            SvcParam::Unknown { ref value, .. } => vec![escaped(value)],
+        };
+
+        write!(f, "=\"{}\"", values.join(","))
+    }
+}
@@ -0,0 +1,32 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use crate::byte_packet_buffer::BytePacketBuffer;
+use crate::error::Result;
+
+/* == TCP framing == */
+// Over TCP every message is preceded by its length as a two byte
+// integer (RFC 7766)
+
+/// Read a single length-prefixed message from the stream
+pub fn read_message(stream: &mut TcpStream) -> Result<BytePacketBuffer> {
+    let mut len_bytes = [0u8; 2];
+    stream.read_exact(&mut len_bytes)?;
+    let len = u16::from_be_bytes(len_bytes) as usize;
+
+    let mut buffer = BytePacketBuffer::with_size(len);
+    stream.read_exact(&mut buffer.buf)?;
+
+    Ok(buffer)
+}
+
+/// Write the first 'len' bytes of the buffer as a length-prefixed message
+pub fn write_message(stream: &mut TcpStream, buffer: &BytePacketBuffer, len: usize) -> Result<()> {
+    let mut message = Vec::with_capacity(len + 2);
+    message.extend_from_slice(&(len as u16).to_be_bytes());
+    message.extend_from_slice(buffer.get_range(0, len)?);
+
+    // A single write keeps the length and the message in the same segment
+    stream.write_all(&message)?;
+
+    Ok(())
+}
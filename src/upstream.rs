@@ -0,0 +1,94 @@
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::dns_packet::DnsPacket;
+use crate::query_type::QueryType;
+use crate::resolver::{lookup, scrub, RetryPolicy};
+use crate::result_code::ResultCode;
+use crate::log::{log_info, log_warn};
+use crate::error::{Error, Result};
+
+/* == Upstreams == */
+
+/// Round trip time counted for a server that failed to answer
+const FAILURE_RTT: Duration = Duration::from_secs(10);
+
+/// Servers the queries are forwarded to, instead of being resolved from the
+/// root servers. Each one has a smoothed round trip time (SRTT) and the
+/// fastest is asked first, like BIND does with the servers of a zone.
+#[derive(Debug)]
+pub struct Upstreams {
+    servers: Vec<SocketAddr>,
+    srtt: Mutex<Vec<Duration>> // same order as 'servers', zero until asked once
+}
+
+impl Upstreams {
+    pub fn new(servers: Vec<SocketAddr>) -> Upstreams {
+        let srtt = Mutex::new(vec![Duration::ZERO; servers.len()]);
+        Upstreams {
+            servers,
+            srtt
+        }
+    }
+
+    /// Whether queries are resolved from the root servers instead
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+
+    /// Indices of the servers, the fastest first. Servers never asked come
+    /// first of all, so every one of them gets measured.
+    fn ranked(&self) -> Vec<usize> {
+        let srtt = self.srtt.lock().unwrap();
+        let mut order: Vec<usize> = (0..self.servers.len()).collect();
+        order.sort_by_key(|&i| srtt[i]);
+        order
+    }
+
+    /// Account for a round trip of 'rtt' to server 'i'. The other servers
+    /// are made to look a little faster, so that a server which failed once
+    /// is eventually given another chance.
+    fn update(&self, i: usize, rtt: Duration) {
+        let mut srtt = self.srtt.lock().unwrap();
+        for (j, value) in srtt.iter_mut().enumerate() {
+            if j == i {
+                *value = if value.is_zero() { rtt } else { (*value * 7 + rtt) / 8 };
+            } else {
+                *value = *value * 49 / 50;
+            }
+        }
+    }
+
+    /// Forward a query to the fastest server, failing over to the next one
+    /// when it doesn't answer in time or answers with SERVFAIL or REFUSED
+    pub fn forward(&self, qname: &str, qtype: QueryType, policy: &RetryPolicy) -> Result<DnsPacket> {
+        let mut last_err = Error::Upstream("No upstream servers".to_string());
+
+        for i in self.ranked() {
+            let server = self.servers[i];
+            log_info!("Forwarding {:?} {} to {}", qtype, qname, server);
+
+            let start = Instant::now();
+            match lookup(qname, qtype, server, true, policy) {
+                Ok(response) if matches!(response.header.rescode, ResultCode::SERVFAIL | ResultCode::REFUSED) => {
+                    self.update(i, FAILURE_RTT);
+                    last_err = Error::Upstream(format!("{:?} from {}", response.header.rescode, server));
+                }
+                Ok(mut response) => {
+                    self.update(i, start.elapsed());
+                    // The servers resolve any name for us, but still must
+                    // not add records for names we didn't ask about
+                    scrub(&mut response, qname, "", server);
+                    return Ok(response);
+                }
+                Err(e) => {
+                    self.update(i, FAILURE_RTT);
+                    last_err = e;
+                }
+            }
+            log_warn!("Upstream {} failed: {}", server, last_err);
+        }
+
+        Err(last_err)
+    }
+}